@@ -0,0 +1,151 @@
+// audit.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::sync::OnceLock;
+
+use git2::{Delta, Diff, DiffFormat};
+use regex_automata::meta::Regex;
+use serde::Serialize;
+
+/// A risky change in the incoming diff of a repository
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub file: String,
+    /// What the change does, like "runs sudo"
+    pub reason: &'static str,
+    /// The added line, or nothing if the whole file is the finding
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub line: String,
+}
+
+/// A pattern of added lines worth a closer look
+struct Rule {
+    reason: &'static str,
+    pattern: &'static str,
+    /// Only check the PKGBUILD, as the pattern is about its variables
+    pkgbuild_only: bool,
+}
+
+const RULES: [Rule; 7] = [
+    Rule {
+        reason: "downloads and runs a script",
+        pattern: r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z|da)?sh\b",
+        pkgbuild_only: false,
+    },
+    Rule {
+        reason: "runs sudo",
+        pattern: r"\bsudo\b",
+        pkgbuild_only: false,
+    },
+    Rule {
+        reason: "changes the sources",
+        pattern: r"^\s*source(_\w+)?\+?=",
+        pkgbuild_only: true,
+    },
+    // Entries of source arrays spanning several lines
+    Rule {
+        reason: "changes a source URL",
+        pattern: r#"^\s*["']?[^=\s]*(https?|ftp|git|git\+\w+|svn\+\w+|hg\+\w+)://"#,
+        pkgbuild_only: true,
+    },
+    Rule {
+        reason: "changes the checksums",
+        pattern: r"^\s*(ck|md5|sha1|sha224|sha256|sha384|sha512|b2)sums(_\w+)?\+?=",
+        pkgbuild_only: true,
+    },
+    // Entries of checksum arrays spanning several lines
+    Rule {
+        reason: "changes the checksums",
+        pattern: r#"^\s*["']?[0-9a-f]{32,128}["']?\s*\)?\s*$"#,
+        pkgbuild_only: true,
+    },
+    Rule {
+        reason: "adds an install scriptlet",
+        pattern: r"^\s*install=",
+        pkgbuild_only: true,
+    },
+];
+
+fn rules() -> &'static [(&'static Rule, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static Rule, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        RULES
+            .iter()
+            .map(|rule| {
+                (
+                    rule,
+                    Regex::new(rule.pattern).expect("invalid audit pattern"),
+                )
+            })
+            .collect()
+    })
+}
+
+/// Returns the risky changes of the diff, in the order they appear
+///
+/// This only points out what deserves a closer look, harmless changes like a version bump
+/// with new checksums are findings as well.
+pub fn audit(diff: &Diff) -> Result<Vec<Finding>, git2::Error> {
+    let mut findings = Vec::new();
+
+    for delta in diff.deltas() {
+        let path = delta
+            .new_file()
+            .path()
+            .unwrap_or_else(|| std::path::Path::new(""));
+        let is_install = path.extension().is_some_and(|ext| ext == "install");
+        if delta.status() == Delta::Added && is_install {
+            findings.push(Finding {
+                file: path.to_string_lossy().to_string(),
+                reason: "adds an install scriptlet",
+                line: String::new(),
+            });
+        }
+    }
+
+    diff.print(DiffFormat::Patch, |delta, _, line| {
+        if line.origin() != '+' {
+            return true;
+        }
+
+        let path = delta
+            .new_file()
+            .path()
+            .unwrap_or_else(|| std::path::Path::new(""));
+        let is_pkgbuild = path.file_name().is_some_and(|name| name == "PKGBUILD");
+        let content = String::from_utf8_lossy(line.content());
+        let content = content.trim_end();
+
+        for (rule, regex) in rules() {
+            if (is_pkgbuild || !rule.pkgbuild_only) && regex.is_match(content) {
+                let finding = Finding {
+                    file: path.to_string_lossy().to_string(),
+                    reason: rule.reason,
+                    line: content.trim().to_string(),
+                };
+                if !findings.contains(&finding) {
+                    findings.push(finding);
+                }
+            }
+        }
+        true
+    })?;
+
+    Ok(findings)
+}
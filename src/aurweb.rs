@@ -0,0 +1,102 @@
+// aurweb.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind};
+
+use reqwest::header::{COOKIE, LOCATION};
+use reqwest::redirect::Policy;
+use reqwest::Client;
+
+const AUR_URL: &str = "https://aur.archlinux.org";
+
+/// A logged-in session on the AUR web interface
+///
+/// Voting and flagging are not part of the RPC interface, so they are done by submitting
+/// the same forms the website uses, authenticated with the `AURSID` session cookie.
+pub struct AurSession {
+    client: Client,
+    cookie: String,
+}
+
+impl AurSession {
+    pub fn new(session_id: Option<&String>) -> Result<AurSession, Error> {
+        let session_id = session_id.ok_or_else(|| {
+            Error::new(
+                ErrorKind::PermissionDenied,
+                "No AUR credentials configured: run 'taur login' or set aur_session in the \
+                 config to the value of the AURSID cookie of a logged-in AUR session",
+            )
+        })?;
+
+        // Redirects tell whether the action succeeded, so they must not be followed
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .map_err(Error::other)?;
+
+        Ok(AurSession {
+            client,
+            cookie: format!("AURSID={}", session_id),
+        })
+    }
+
+    pub async fn vote(&self, package_base: &str) -> Result<(), Error> {
+        self.post(&format!("pkgbase/{}/vote", package_base), &[])
+            .await
+    }
+
+    pub async fn flag(&self, package_base: &str, comment: &str) -> Result<(), Error> {
+        self.post(
+            &format!("pkgbase/{}/flag", package_base),
+            &[("comments", comment)],
+        )
+        .await
+    }
+
+    async fn post(&self, path: &str, form: &[(&str, &str)]) -> Result<(), Error> {
+        let response = self
+            .client
+            .post(format!("{}/{}", AUR_URL, path))
+            .header(COOKIE, &self.cookie)
+            .form(form)
+            .send()
+            .await
+            .map_err(Error::other)?;
+
+        // The AUR sends expired or invalid sessions to the login page
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .unwrap_or_default();
+        if location.contains("/login") {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "The AUR session is invalid or expired, update it with 'taur login'",
+            ));
+        }
+
+        let status = response.status();
+        if !status.is_success() && !status.is_redirection() {
+            return Err(Error::other(format!("The AUR responded with {}", status)));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+// build.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::srcinfo::Srcinfo;
+
+/// How packages are built
+#[derive(Clone, Default)]
+pub struct BuildOptions {
+    pub no_confirm: bool,
+    /// Rebuild from a clean source directory even if the package was already built
+    pub rebuild: bool,
+    /// Don't let makepkg install missing dependencies
+    pub no_deps: bool,
+    /// Build with makechrootpkg in this chroot instead of running makepkg directly
+    pub chroot: Option<PathBuf>,
+    /// Directory the built packages are put into (`PKGDEST`) instead of the repository
+    pub output_dir: Option<PathBuf>,
+    pub tools: Tools,
+    /// Extra makepkg arguments of single packages, by repository name
+    pub makepkg_args: BTreeMap<String, Vec<String>>,
+}
+
+impl BuildOptions {
+    /// Checks everything the build needs before any work is done, creating the output directory
+    /// if it is missing
+    pub fn check(&self) -> Result<(), Error> {
+        self.tools.check()?;
+        if let Some(chroot) = &self.chroot {
+            check_chroot(chroot)?;
+        }
+        if let Some(dir) = &self.output_dir {
+            check_output_dir(dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the extra makepkg arguments of the given package
+    pub fn makepkg_args(&self, name: &str) -> &[String] {
+        self.makepkg_args.get(name).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Programs run for building and installing, for systems where they are not on the `PATH` or
+/// have to be wrapped
+#[derive(Clone, Debug)]
+pub struct Tools {
+    pub makepkg: PathBuf,
+    pub pacman: PathBuf,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Tools {
+            makepkg: PathBuf::from("makepkg"),
+            pacman: PathBuf::from("pacman"),
+        }
+    }
+}
+
+impl Tools {
+    /// Checks that makepkg and pacman can be run, so a wrong path fails before any work is done
+    pub fn check(&self) -> Result<(), Error> {
+        check_executable(&self.makepkg, "makepkg")?;
+        check_executable(&self.pacman, "pacman")
+    }
+}
+
+/// Checks that the program is an executable file, looking it up in `PATH` if it is only a name
+pub fn check_executable(program: &Path, name: &str) -> Result<(), Error> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+
+    let found = if program.components().count() > 1 {
+        is_executable(program)
+    } else {
+        std::env::var_os("PATH").is_some_and(|paths| {
+            std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program)))
+        })
+    };
+
+    if !found {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "{} {:?} is not an executable (set another path with --{}-path or `{}_path` \
+                 in the config)",
+                name, program, name, name
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Creates the output directory if needed and checks that packages can be written into it
+fn check_output_dir(dir: &Path) -> Result<(), Error> {
+    let unusable = |e: Error| {
+        Error::new(
+            e.kind(),
+            format!("Unable to write packages into {:?}: {}", dir, e),
+        )
+    };
+
+    std::fs::create_dir_all(dir).map_err(unusable)?;
+    // Permissions don't tell about read-only mounts or ACLs, so actually try to write
+    let probe = dir.join(".taur-write-test");
+    std::fs::write(&probe, b"").map_err(unusable)?;
+    std::fs::remove_file(&probe).map_err(unusable)
+}
+
+/// Checks that the given chroot was created (with mkarchroot) and can be used for building
+pub fn check_chroot(chroot: &Path) -> Result<(), Error> {
+    if !chroot.join("root").is_dir() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "No chroot found at {:?}, create it with `mkarchroot {:?} base-devel` \
+                 (part of the devtools package)",
+                chroot,
+                chroot.join("root")
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Builds the package in the given repository with makepkg or makechrootpkg
+///
+/// Missing dependencies are installed by makepkg itself. In a chroot, the given package files
+/// built earlier are installed as well, so AUR dependencies can be satisfied. Returns the paths
+/// of the built package files. `extra_args` are passed to makepkg after taur's own arguments.
+pub fn build_package(
+    repo: &Path,
+    options: &BuildOptions,
+    built: &[PathBuf],
+    extra_args: &[String],
+) -> Result<Vec<PathBuf>, Error> {
+    match &options.chroot {
+        Some(chroot) => {
+            let mut makechrootpkg = Command::new("makechrootpkg");
+            makechrootpkg
+                .current_dir(repo)
+                .arg("-c")
+                .arg("-r")
+                .arg(chroot);
+            for file in built {
+                makechrootpkg.arg("-I").arg(file);
+            }
+            if let Some(dir) = &options.output_dir {
+                makechrootpkg.env("PKGDEST", dir);
+            }
+            // Everything after `--` is passed on to makepkg
+            if options.rebuild || !extra_args.is_empty() {
+                makechrootpkg.arg("--");
+            }
+            if options.rebuild {
+                makechrootpkg.args(["--force", "--cleanbuild"]);
+            }
+            makechrootpkg.args(extra_args);
+            run(&mut makechrootpkg, "makechrootpkg")?;
+        }
+        None => {
+            let mut makepkg = Command::new(&options.tools.makepkg);
+            makepkg.current_dir(repo);
+            if let Some(dir) = &options.output_dir {
+                makepkg.env("PKGDEST", dir);
+            }
+            if !options.no_deps {
+                makepkg.arg("--syncdeps");
+            }
+            if options.no_confirm {
+                makepkg.arg("--noconfirm");
+            }
+            if options.rebuild {
+                makepkg.args(["--force", "--cleanbuild"]);
+            }
+            makepkg.args(extra_args);
+            run(&mut makepkg, "makepkg")?;
+        }
+    }
+
+    package_list(repo, options)
+}
+
+/// Returns the package files of the current version if they were already built
+///
+/// The version from `.SRCINFO` is compared with the file names of the packages in the
+/// repository (or the output directory), so this works without running makepkg. Returns None
+/// if any package of the package base is missing (or `.SRCINFO` can't be read), as it has to
+/// be built then.
+pub fn already_built(repo: &Path, output_dir: Option<&Path>) -> Option<Vec<PathBuf>> {
+    let srcinfo = Srcinfo::read(repo).ok()?;
+    let version = srcinfo.version()?;
+
+    let mut files = Vec::new();
+    let entries: Vec<PathBuf> = std::fs::read_dir(output_dir.unwrap_or(repo))
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    for pkgname in &srcinfo.pkgnames {
+        let file = entries.iter().find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_package_file)
+                .is_some_and(|(name, v)| name == pkgname && v == version)
+        })?;
+        files.push(file.clone());
+    }
+
+    Some(files)
+}
+
+/// Splits a package file name like `foo-1:2.0-3-x86_64.pkg.tar.zst` into name and version
+fn parse_package_file(file_name: &str) -> Option<(&str, &str)> {
+    if file_name.ends_with(".sig") {
+        return None;
+    }
+
+    let (stem, _) = file_name.split_once(".pkg.tar")?;
+    let mut parts = stem.rsplitn(4, '-');
+    let arch = parts.next()?;
+    let _pkgrel = parts.next()?;
+    let _pkgver = parts.next()?;
+    let name = parts.next()?;
+
+    Some((name, &stem[name.len() + 1..stem.len() - arch.len() - 1]))
+}
+
+/// Returns what makepkg leaves in the repository: the `src` and `pkg` directories, built
+/// package files, logs and downloaded sources
+///
+/// Only the names are checked, so the caller has to leave out files tracked by git.
+pub fn artifacts(repo: &Path) -> Result<Vec<PathBuf>, Error> {
+    let downloaded = Srcinfo::read(repo)
+        .map(|srcinfo| srcinfo.downloaded_files())
+        .unwrap_or_default();
+
+    let mut artifacts = Vec::new();
+    for entry in std::fs::read_dir(repo)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let is_artifact = match name {
+            "src" | "pkg" => path.is_dir(),
+            _ => {
+                name.contains(".pkg.tar")
+                    || name.ends_with(".log")
+                    || downloaded.iter().any(|file| file == name)
+            }
+        };
+        if is_artifact {
+            artifacts.push(path);
+        }
+    }
+    artifacts.sort();
+
+    Ok(artifacts)
+}
+
+/// Downloads the sources of the package and checks them against their checksums (and
+/// signatures) with `makepkg --verifysource`, without building anything
+pub fn verify_sources(repo: &Path, tools: &Tools) -> Result<(), Error> {
+    let mut makepkg = Command::new(&tools.makepkg);
+    makepkg.current_dir(repo).arg("--verifysource");
+
+    run(&mut makepkg, "makepkg --verifysource")
+}
+
+/// Installs the given package files with `pacman -U`
+pub fn install_packages(files: &[PathBuf], options: &BuildOptions) -> Result<(), Error> {
+    let mut pacman = Command::new("sudo");
+    pacman.arg(&options.tools.pacman).arg("-U");
+    if options.no_confirm {
+        pacman.arg("--noconfirm");
+    }
+    pacman.args(files);
+
+    run(&mut pacman, "pacman")
+}
+
+/// Uninstalls the given packages with `pacman -R`
+pub fn uninstall_packages(names: &[String], no_confirm: bool, pacman: &Path) -> Result<(), Error> {
+    let mut command = Command::new("sudo");
+    command.arg(pacman).arg("-R");
+    if no_confirm {
+        command.arg("--noconfirm");
+    }
+    command.args(names);
+
+    run(&mut command, "pacman")
+}
+
+/// Returns the names of all installed packages, or only of those not found in any sync
+/// database (`pacman -Qm`, which is how packages built from AUR show up)
+pub fn installed_packages(foreign_only: bool, pacman: &Path) -> Result<HashSet<String>, Error> {
+    let flags = if foreign_only { "-Qqm" } else { "-Qq" };
+
+    Ok(query(pacman, flags)?.lines().map(str::to_string).collect())
+}
+
+/// Returns the installed versions of all packages that are not in a sync database
+pub fn foreign_versions(pacman: &Path) -> Result<BTreeMap<String, String>, Error> {
+    Ok(query(pacman, "-Qm")?
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect())
+}
+
+/// Runs a pacman query and returns its output
+fn query(pacman: &Path, flags: &str) -> Result<String, Error> {
+    let output = Command::new(pacman)
+        .arg(flags)
+        .output()
+        .map_err(|e| Error::new(e.kind(), format!("Unable to run pacman: {}", e)))?;
+
+    // pacman -Qm exits with 1 if there are no foreign packages at all
+    if !output.status.success() && !output.stderr.is_empty() {
+        return Err(Error::other(format!(
+            "pacman {} failed: {}",
+            flags,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the package files makepkg produces for the given repository
+fn package_list(repo: &Path, options: &BuildOptions) -> Result<Vec<PathBuf>, Error> {
+    let mut makepkg = Command::new(&options.tools.makepkg);
+    makepkg.current_dir(repo).arg("--packagelist");
+    if let Some(dir) = &options.output_dir {
+        makepkg.env("PKGDEST", dir);
+    }
+    let output = makepkg.output()?;
+
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "makepkg --packagelist failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .collect())
+}
+
+fn run(command: &mut Command, name: &str) -> Result<(), Error> {
+    let status = command
+        .status()
+        .map_err(|e| Error::new(e.kind(), format!("Unable to run {}: {}", name, e)))?;
+
+    if !status.success() {
+        return Err(Error::other(format!("{} exited with {}", name, status)));
+    }
+
+    Ok(())
+}
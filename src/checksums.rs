@@ -0,0 +1,108 @@
+// checksums.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::srcinfo::Srcinfo;
+
+/// Algorithms makepkg checks sources with, as used in the `<algorithm>sums` arrays
+const ALGORITHMS: [&str; 8] = [
+    "ck", "md5", "sha1", "sha224", "sha256", "sha384", "sha512", "b2",
+];
+
+/// Problems with the checksums declared in `.SRCINFO`
+#[derive(Debug, Default)]
+pub struct ChecksumReport {
+    /// Sources without any checksum and arrays not matching their sources
+    pub problems: Vec<String>,
+    /// Sources whose checksums are all `SKIP`, so they are downloaded unchecked
+    pub skipped: Vec<String>,
+}
+
+/// Checks that every source has a checksum
+///
+/// Each architecture (and the sources for all of them) is checked on its own, as
+/// `source_x86_64` gets its checksums from `sha256sums_x86_64` and so on. `SKIP` is common for
+/// VCS sources, which can't have a checksum, so those are only reported as skipped.
+pub fn check(srcinfo: &Srcinfo) -> ChecksumReport {
+    let mut report = ChecksumReport::default();
+
+    for (key, sources) in &srcinfo.source_arrays {
+        let suffix = match key.strip_prefix("source") {
+            Some(suffix) => suffix,
+            None => continue,
+        };
+
+        let arrays: Vec<(String, &Vec<String>)> = ALGORITHMS
+            .iter()
+            .filter_map(|algorithm| {
+                let key = format!("{}sums{}", algorithm, suffix);
+                let array = srcinfo.source_arrays.get(&key)?;
+                Some((key, array))
+            })
+            .collect();
+
+        if arrays.is_empty() {
+            for source in sources {
+                report
+                    .problems
+                    .push(format!("{} has no checksum", source_name(source)));
+            }
+            continue;
+        }
+
+        for (key, array) in &arrays {
+            if array.len() != sources.len() {
+                report.problems.push(format!(
+                    "{} has {} entries for {} {}",
+                    key,
+                    array.len(),
+                    sources.len(),
+                    if sources.len() == 1 {
+                        "source"
+                    } else {
+                        "sources"
+                    }
+                ));
+            }
+        }
+
+        for (index, source) in sources.iter().enumerate() {
+            let sums: Vec<&String> = arrays
+                .iter()
+                .filter_map(|(_, array)| array.get(index))
+                .collect();
+            if sums.is_empty() {
+                report
+                    .problems
+                    .push(format!("{} has no checksum", source_name(source)));
+            } else if sums.iter().all(|sum| *sum == "SKIP") {
+                report.skipped.push(source_name(source).to_string());
+            }
+        }
+    }
+
+    report
+}
+
+/// Returns the file name a source is saved as, like `foo.tar.gz` for `foo.tar.gz::https://...`
+fn source_name(source: &str) -> &str {
+    match source.split_once("::") {
+        Some((name, _)) => name,
+        None => source.rsplit('/').next().unwrap_or(source),
+    }
+}
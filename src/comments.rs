@@ -0,0 +1,168 @@
+// comments.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// How long a downloaded package page is reused before fetching it again
+const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Serialize)]
+pub struct Comment {
+    pub id: u64,
+    pub author: String,
+    pub date: String,
+    pub text: String,
+}
+
+/// Returns the HTML of the AUR package page, using a cached copy while it is fresh
+///
+/// The AUR RPC does not expose comments, so they have to be read from the package page.
+pub async fn fetch_page(
+    client: &reqwest::Client,
+    cache_dir: &Path,
+    package_name: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let cache_file = cache_dir.join(format!("{}.html", package_name));
+
+    let fresh = std::fs::metadata(&cache_file)
+        .and_then(|meta| meta.modified())
+        .map(|modified| modified.elapsed().unwrap_or_default() < CACHE_TTL)
+        .unwrap_or(false);
+    if fresh {
+        return Ok(std::fs::read_to_string(&cache_file)?);
+    }
+
+    let url = format!("https://aur.archlinux.org/packages/{}", package_name);
+    let response = client.get(&url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("Package '{}' not found", package_name),
+        )));
+    }
+    let html = response.error_for_status()?.text().await?;
+
+    // Failing to cache the page is no reason to fail the command
+    if std::fs::create_dir_all(cache_dir).is_ok() {
+        let _ = std::fs::write(&cache_file, &html);
+    }
+
+    Ok(html)
+}
+
+/// Extracts the comments from an AUR package page, newest first
+pub fn parse(html: &str) -> Vec<Comment> {
+    let mut comments: Vec<Comment> = Vec::new();
+
+    let mut rest = html;
+    while let Some(start) = rest.find("<h4 id=\"comment-") {
+        rest = &rest[start + "<h4 id=\"comment-".len()..];
+
+        let id = match rest.split('"').next().and_then(|id| id.parse::<u64>().ok()) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let header_end = rest.find("</h4>").unwrap_or(rest.len());
+        let header = &rest[..header_end];
+        rest = &rest[header_end..];
+
+        // The header reads "<a>author</a> commented on <a class="date">date</a>"
+        let author = match header.find("<a") {
+            Some(index) => element_text(&header[index..]),
+            None => strip_tags(header.split("commented on").next().unwrap_or_default()),
+        };
+        let date = header
+            .find("class=\"date\"")
+            .map(|index| element_text(&header[index..]))
+            .unwrap_or_default();
+
+        let content_end = rest.find("<h4").unwrap_or(rest.len());
+        let text = strip_tags(&rest[..content_end]);
+
+        // Pinned comments are listed twice on the page
+        if !comments.iter().any(|c| c.id == id) {
+            comments.push(Comment {
+                id,
+                author: author.trim().to_string(),
+                date: date.trim().to_string(),
+                text,
+            });
+        }
+    }
+
+    comments.sort_unstable_by_key(|c| std::cmp::Reverse(c.id));
+    comments
+}
+
+/// Returns the text of the element starting somewhere before the next `>`
+fn element_text(html: &str) -> String {
+    let start = match html.find('>') {
+        Some(index) => index + 1,
+        None => return String::new(),
+    };
+    let end = html[start..]
+        .find('<')
+        .map_or(html.len(), |index| start + index);
+
+    decode_entities(&html[start..end])
+}
+
+/// Converts an HTML fragment to plain text, keeping paragraphs and line breaks
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |index| start + index + 1);
+
+        let tag = rest[start..end].to_ascii_lowercase();
+        if tag.starts_with("<br") || tag.starts_with("</p") {
+            text.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    lines.join("\n")
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,660 @@
+// completions.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::fmt::Write;
+
+use clap::{ValueEnum, ValueHint};
+
+/// Value name of positional arguments taking AUR packages, which are completed from the AUR
+/// instead of the cloned repositories
+pub const AUR_PACKAGE: &str = "AUR_PACKAGE";
+
+/// Shells completion scripts can be generated for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// An option of a command, as far as completion cares
+struct Flag {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    values: Values,
+}
+
+impl Flag {
+    fn names(&self) -> Vec<String> {
+        let long = self.long.iter().map(|long| format!("--{}", long));
+        let short = self.short.iter().map(|short| format!("-{}", short));
+        long.chain(short).collect()
+    }
+}
+
+/// What the value of an option is completed with
+#[derive(Clone, PartialEq)]
+enum Values {
+    /// The option is a flag without a value
+    None,
+    /// Any text, nothing to suggest
+    Any,
+    Paths,
+    Choices(Vec<String>),
+}
+
+/// Which names the positional arguments of a command are completed with
+#[derive(Clone, Copy, PartialEq)]
+enum Packages {
+    None,
+    Cloned,
+    Aur,
+}
+
+struct Subcommand {
+    name: String,
+    about: String,
+    flags: Vec<Flag>,
+    packages: Packages,
+    /// Possible values of the positional arguments, like the shells of `completions`
+    choices: Vec<String>,
+    /// Names and descriptions of nested subcommands, like those of `config`
+    subcommands: Vec<(String, String)>,
+}
+
+/// Returns the completion script for the shell, covering the subcommands and options of the
+/// command and the package names from `taur __complete`
+pub fn generate(shell: Shell, mut command: clap::Command) -> String {
+    // Building propagates the global options and adds help to every subcommand
+    command.build();
+    let flags = flags_of(&command);
+    let subcommands: Vec<Subcommand> = command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .map(describe)
+        .collect();
+
+    match shell {
+        Shell::Bash => bash(&flags, &subcommands),
+        Shell::Zsh => zsh(&flags, &subcommands),
+        Shell::Fish => fish(&flags, &subcommands),
+    }
+}
+
+fn describe(command: &clap::Command) -> Subcommand {
+    let name = command.get_name().to_string();
+    let packages = command
+        .get_positionals()
+        .filter(|arg| matches!(arg.get_id().as_str(), "package_name" | "package_names"))
+        .map(|arg| match arg.get_value_names() {
+            Some([value_name]) if value_name == AUR_PACKAGE => Packages::Aur,
+            _ => Packages::Cloned,
+        })
+        .next()
+        .unwrap_or(Packages::None);
+
+    Subcommand {
+        about: about(command),
+        flags: flags_of(command),
+        packages,
+        choices: command
+            .get_positionals()
+            .flat_map(|arg| arg.get_possible_values())
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect(),
+        subcommands: command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| (sub.get_name().to_string(), about(sub)))
+            .collect(),
+        name,
+    }
+}
+
+fn about(command: &clap::Command) -> String {
+    command
+        .get_about()
+        .map(|about| first_line(&about.to_string()))
+        .unwrap_or_default()
+}
+
+fn flags_of(command: &clap::Command) -> Vec<Flag> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set() && !arg.is_positional())
+        .map(|arg| Flag {
+            long: arg.get_long().map(str::to_string),
+            short: arg.get_short(),
+            help: arg
+                .get_help()
+                .map(|help| first_line(&help.to_string()))
+                .unwrap_or_default(),
+            values: values_of(arg),
+        })
+        .collect()
+}
+
+fn values_of(arg: &clap::Arg) -> Values {
+    if !arg.get_action().takes_values() {
+        return Values::None;
+    }
+
+    let choices: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !choices.is_empty() {
+        return Values::Choices(choices);
+    }
+
+    match arg.get_value_hint() {
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath => Values::Paths,
+        ValueHint::ExecutablePath => Values::Paths,
+        _ => Values::Any,
+    }
+}
+
+/// Returns the first line of a help text without a trailing remark in parentheses, which is
+/// all that fits into completion menus
+fn first_line(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default().trim();
+    match line.rsplit_once(" (") {
+        Some((start, _)) if line.ends_with(')') => start.to_string(),
+        _ => line.to_string(),
+    }
+}
+
+/// Quotes text for the shell with single quotes
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Returns the names of all options taking a value, with what the value is completed with
+///
+/// Options of the same name in several subcommands are expected to take the same values, the
+/// first one wins.
+fn valued(flags: &[Flag], subcommands: &[Subcommand]) -> Vec<(String, Values)> {
+    let mut valued: Vec<(String, Values)> = Vec::new();
+    let all = flags
+        .iter()
+        .chain(subcommands.iter().flat_map(|sub| &sub.flags));
+    for flag in all.filter(|flag| flag.values != Values::None) {
+        for name in flag.names() {
+            if !valued.iter().any(|(n, _)| *n == name) {
+                valued.push((name, flag.values.clone()));
+            }
+        }
+    }
+
+    valued
+}
+
+fn flag_names(flags: &[Flag]) -> String {
+    flags
+        .iter()
+        .flat_map(Flag::names)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn bash(flags: &[Flag], subcommands: &[Subcommand]) -> String {
+    let valued = valued(flags, subcommands);
+    let names: Vec<&str> = valued.iter().map(|(name, _)| name.as_str()).collect();
+    let commands: Vec<&str> = subcommands.iter().map(|sub| sub.name.as_str()).collect();
+
+    let mut script = String::new();
+    let _ = writeln!(script, "_taur() {{");
+    let _ = writeln!(
+        script,
+        "    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}"
+    );
+    let _ = writeln!(script, "    local valued=\" {} \"", names.join(" "));
+    // The first word that is neither an option nor the value of one is the subcommand
+    let _ = writeln!(script, "    local cmd= sub= i");
+    let _ = writeln!(script, "    for ((i = 1; i < COMP_CWORD; i++)); do");
+    let _ = writeln!(script, "        case ${{COMP_WORDS[i]}} in");
+    let _ = writeln!(
+        script,
+        "            -*) [[ $valued == *\" ${{COMP_WORDS[i]}} \"* ]] && ((i++)) ;;"
+    );
+    let _ = writeln!(
+        script,
+        "            *) if [[ -z $cmd ]]; then cmd=${{COMP_WORDS[i]}}; elif [[ -z $sub ]]; \
+         then sub=${{COMP_WORDS[i]}}; fi ;;"
+    );
+    let _ = writeln!(script, "        esac");
+    let _ = writeln!(script, "    done");
+    let _ = writeln!(script);
+
+    let _ = writeln!(script, "    case $prev in");
+    for (name, values) in &valued {
+        let reply = match values {
+            Values::Paths => String::from("$(compgen -f -- \"$cur\")"),
+            Values::Choices(choices) => {
+                format!("$(compgen -W \"{}\" -- \"$cur\")", choices.join(" "))
+            }
+            Values::Any | Values::None => String::new(),
+        };
+        let _ = writeln!(script, "        {}) COMPREPLY=({}); return ;;", name, reply);
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script);
+
+    // Options are only suggested once a dash is typed, like git does
+    let _ = writeln!(script, "    local words");
+    let _ = writeln!(script, "    case $cmd in");
+    let _ = writeln!(script, "        \"\")");
+    let _ = writeln!(
+        script,
+        "            [[ $cur == -* ]] && words=\"{}\" || words=\"{}\"",
+        flag_names(flags),
+        commands.join(" ")
+    );
+    let _ = writeln!(script, "            ;;");
+    for sub in subcommands {
+        let _ = writeln!(script, "        {})", sub.name);
+        let _ = writeln!(script, "            if [[ $cur == -* ]]; then");
+        let _ = writeln!(
+            script,
+            "                words=\"{}\"",
+            flag_names(&sub.flags)
+        );
+        let _ = writeln!(script, "            else");
+        let _ = writeln!(
+            script,
+            "                words=\"{}\"",
+            sub.choices.join(" ")
+        );
+        let positional = match sub.packages {
+            Packages::Aur => Some("$(taur __complete --aur \"$cur\")"),
+            Packages::Cloned => Some("$(taur __complete \"$cur\")"),
+            Packages::None => None,
+        };
+        if let Some(positional) = positional {
+            let _ = writeln!(script, "                COMPREPLY=({}); return", positional);
+        }
+        if !sub.subcommands.is_empty() {
+            let nested: Vec<&str> = sub.subcommands.iter().map(|(n, _)| n.as_str()).collect();
+            let _ = writeln!(
+                script,
+                "                [[ -z $sub ]] && words=\"{}\"",
+                nested.join(" ")
+            );
+        }
+        let _ = writeln!(script, "            fi");
+        let _ = writeln!(script, "            ;;");
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(
+        script,
+        "    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))"
+    );
+    let _ = writeln!(script, "}}");
+    let _ = writeln!(script, "complete -F _taur taur");
+
+    script
+}
+
+fn zsh(flags: &[Flag], subcommands: &[Subcommand]) -> String {
+    let valued = valued(flags, subcommands);
+    let names: Vec<&str> = valued.iter().map(|(name, _)| name.as_str()).collect();
+
+    let mut script = String::new();
+    let _ = writeln!(script, "#compdef taur");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "_taur() {{");
+    let _ = writeln!(script, "    local -a commands valued");
+    let _ = writeln!(script, "    commands=(");
+    for sub in subcommands {
+        let entry = format!("{}:{}", sub.name, sub.about);
+        let _ = writeln!(script, "        {}", quote(&entry));
+    }
+    let _ = writeln!(script, "    )");
+    let _ = writeln!(script, "    valued=({})", names.join(" "));
+    let _ = writeln!(script);
+    // The first word that is neither an option nor the value of one is the subcommand
+    let _ = writeln!(script, "    local cmd= sub= i");
+    let _ = writeln!(script, "    for ((i = 2; i < CURRENT; i++)); do");
+    let _ = writeln!(script, "        case $words[i] in");
+    let _ = writeln!(
+        script,
+        "            -*) (( ${{valued[(Ie)$words[i]]}} )) && ((i++)) ;;"
+    );
+    let _ = writeln!(
+        script,
+        "            *) if [[ -z $cmd ]]; then cmd=$words[i]; elif [[ -z $sub ]]; then \
+         sub=$words[i]; fi ;;"
+    );
+    let _ = writeln!(script, "        esac");
+    let _ = writeln!(script, "    done");
+    let _ = writeln!(script);
+
+    let _ = writeln!(script, "    case $words[CURRENT-1] in");
+    for (name, values) in &valued {
+        let action = match values {
+            Values::Paths => String::from("_files"),
+            Values::Choices(choices) => format!("compadd -- {}", choices.join(" ")),
+            Values::Any | Values::None => String::from(":"),
+        };
+        let _ = writeln!(script, "        ({}) {}; return ;;", name, action);
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script);
+
+    let _ = writeln!(script, "    case $cmd in");
+    let _ = writeln!(script, "        ('')");
+    let _ = writeln!(
+        script,
+        "            [[ $PREFIX == -* ]] && compadd -- {} || _describe command commands",
+        flag_names(flags)
+    );
+    let _ = writeln!(script, "            ;;");
+    for sub in subcommands {
+        let _ = writeln!(script, "        ({})", sub.name);
+        let positional = match sub.packages {
+            Packages::Aur => Some("${(f)\"$(taur __complete --aur $PREFIX)\"}"),
+            Packages::Cloned => Some("${(f)\"$(taur __complete $PREFIX)\"}"),
+            Packages::None => None,
+        };
+        let nested: Vec<String> = sub
+            .subcommands
+            .iter()
+            .map(|(name, about)| quote(&format!("{}:{}", name, about)))
+            .collect();
+        let mut otherwise = Vec::new();
+        if !sub.choices.is_empty() {
+            otherwise.push(format!("compadd -- {}", sub.choices.join(" ")));
+        }
+        if let Some(positional) = positional {
+            otherwise.push(format!("compadd -- {}", positional));
+        }
+        if !nested.is_empty() {
+            let _ = writeln!(script, "            local -a nested=({})", nested.join(" "));
+            otherwise.push(String::from("[[ -z $sub ]] && _describe subcommand nested"));
+        }
+        match otherwise.is_empty() {
+            true => {
+                let _ = writeln!(script, "            compadd -- {}", flag_names(&sub.flags));
+            }
+            false => {
+                let _ = writeln!(script, "            if [[ $PREFIX == -* ]]; then");
+                let _ = writeln!(
+                    script,
+                    "                compadd -- {}",
+                    flag_names(&sub.flags)
+                );
+                let _ = writeln!(script, "            else");
+                for line in otherwise {
+                    let _ = writeln!(script, "                {}", line);
+                }
+                let _ = writeln!(script, "            fi");
+            }
+        }
+        let _ = writeln!(script, "            ;;");
+    }
+    let _ = writeln!(script, "    esac");
+    let _ = writeln!(script, "}}");
+    let _ = writeln!(script);
+    let _ = writeln!(script, "_taur \"$@\"");
+
+    script
+}
+
+fn fish(flags: &[Flag], subcommands: &[Subcommand]) -> String {
+    let mut script = String::new();
+    let _ = writeln!(script, "complete -c taur -f");
+
+    let top = String::from("__fish_use_subcommand");
+    for flag in flags {
+        let _ = writeln!(script, "{}", fish_flag(&top, flag));
+    }
+    for sub in subcommands {
+        let _ = writeln!(
+            script,
+            "complete -c taur -n {} -a {} -d {}",
+            top,
+            sub.name,
+            quote(&sub.about)
+        );
+    }
+
+    for sub in subcommands {
+        let condition = format!("'__fish_seen_subcommand_from {}'", sub.name);
+        for flag in &sub.flags {
+            let _ = writeln!(script, "{}", fish_flag(&condition, flag));
+        }
+        let positional = match sub.packages {
+            Packages::Aur => Some("'(taur __complete --aur (commandline -ct))'"),
+            Packages::Cloned => Some("'(taur __complete (commandline -ct))'"),
+            Packages::None => None,
+        };
+        if let Some(positional) = positional {
+            let _ = writeln!(
+                script,
+                "complete -c taur -n {} -a {}",
+                condition, positional
+            );
+        }
+        if !sub.choices.is_empty() {
+            let choices = quote(&sub.choices.join(" "));
+            let _ = writeln!(script, "complete -c taur -n {} -a {}", condition, choices);
+        }
+        for (name, about) in &sub.subcommands {
+            let _ = writeln!(
+                script,
+                "complete -c taur -n {} -a {} -d {}",
+                condition,
+                name,
+                quote(about)
+            );
+        }
+    }
+
+    script
+}
+
+fn fish_flag(condition: &str, flag: &Flag) -> String {
+    let mut line = format!("complete -c taur -n {}", condition);
+    if let Some(long) = &flag.long {
+        let _ = write!(line, " -l {}", long);
+    }
+    if let Some(short) = flag.short {
+        let _ = write!(line, " -s {}", short);
+    }
+    match &flag.values {
+        Values::Paths => line.push_str(" -r -F"),
+        Values::Any => line.push_str(" -x"),
+        Values::Choices(choices) => {
+            let _ = write!(line, " -x -a {}", quote(&choices.join(" ")));
+        }
+        Values::None => {}
+    }
+    if !flag.help.is_empty() {
+        let _ = write!(line, " -d {}", quote(&flag.help));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::Args;
+
+    /// Options of a command as clap sees them, like `--force` and `-r`
+    fn options(command: &clap::Command) -> Vec<String> {
+        command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set() && !arg.is_positional())
+            .flat_map(|arg| {
+                let long = arg.get_long().map(|long| format!("--{}", long));
+                let short = arg.get_short().map(|short| format!("-{}", short));
+                long.into_iter().chain(short)
+            })
+            .collect()
+    }
+
+    /// Returns the built command, with the global options propagated to the subcommands
+    fn taur() -> clap::Command {
+        let mut command = Args::command();
+        command.build();
+        command
+    }
+
+    /// Returns the lines from `start` up to the end of its `case` branch
+    fn branch<'a>(script: &'a str, start: &str) -> &'a str {
+        let begin = script
+            .find(start)
+            .unwrap_or_else(|| panic!("no branch {:?}", start));
+        let rest = &script[begin..];
+        let end = rest.find("\n            ;;").unwrap_or(rest.len());
+        &rest[..end]
+    }
+
+    fn words(text: &str) -> Vec<&str> {
+        text.split(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '=')
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    /// Returns the options of fish completions given under the condition, as `--long` and `-s`
+    fn fish_options(script: &str, condition: &str) -> Vec<String> {
+        script
+            .lines()
+            .filter(|line| line.contains(&format!(" -n {} ", condition)))
+            .flat_map(|line| {
+                let words = words(line);
+                words
+                    .windows(2)
+                    .filter_map(|pair| match pair[0] {
+                        "-l" => Some(format!("--{}", pair[1])),
+                        "-s" => Some(format!("-{}", pair[1])),
+                        _ => None,
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .collect()
+    }
+
+    fn visible(command: &clap::Command) -> impl Iterator<Item = &clap::Command> {
+        command.get_subcommands().filter(|sub| !sub.is_hide_set())
+    }
+
+    #[test]
+    fn bash_covers_all_commands_and_options() {
+        let command = taur();
+        let script = generate(Shell::Bash, command.clone());
+
+        let top = words(branch(&script, "        \"\")"));
+        for option in options(&command) {
+            assert!(top.contains(&option.as_str()), "missing {}", option);
+        }
+        for sub in visible(&command) {
+            assert!(top.contains(&sub.get_name()), "missing {}", sub.get_name());
+
+            let branch = words(branch(&script, &format!("\n        {})\n", sub.get_name())));
+            for option in options(sub) {
+                assert!(
+                    branch.contains(&option.as_str()),
+                    "missing {} of {}",
+                    option,
+                    sub.get_name()
+                );
+            }
+            for nested in visible(sub) {
+                assert!(branch.contains(&nested.get_name()));
+            }
+        }
+    }
+
+    #[test]
+    fn zsh_covers_all_commands_and_options() {
+        let command = taur();
+        let script = generate(Shell::Zsh, command.clone());
+
+        let top = words(branch(&script, "        ('')"));
+        for option in options(&command) {
+            assert!(top.contains(&option.as_str()), "missing {}", option);
+        }
+        for sub in visible(&command) {
+            let entry = format!("        '{}:", sub.get_name());
+            assert!(script.contains(&entry), "missing {}", sub.get_name());
+
+            let branch = branch(&script, &format!("        ({})\n", sub.get_name()));
+            let branch_words = words(branch);
+            for option in options(sub) {
+                assert!(
+                    branch_words.contains(&option.as_str()),
+                    "missing {} of {}",
+                    option,
+                    sub.get_name()
+                );
+            }
+            for nested in visible(sub) {
+                assert!(branch.contains(&format!("'{}:", nested.get_name())));
+            }
+        }
+    }
+
+    #[test]
+    fn fish_covers_all_commands_and_options() {
+        let command = taur();
+        let script = generate(Shell::Fish, command.clone());
+
+        let top = fish_options(&script, "__fish_use_subcommand");
+        for option in options(&command) {
+            assert!(top.contains(&option), "missing {}", option);
+        }
+        for sub in visible(&command) {
+            let entry = format!("-n __fish_use_subcommand -a {} ", sub.get_name());
+            assert!(script.contains(&entry), "missing {}", sub.get_name());
+
+            let condition = format!("'__fish_seen_subcommand_from {}'", sub.get_name());
+            let branch = fish_options(&script, &condition);
+            for option in options(sub) {
+                assert!(
+                    branch.contains(&option),
+                    "missing {} of {}",
+                    option,
+                    sub.get_name()
+                );
+            }
+            for nested in visible(sub) {
+                let entry = format!("-n {} -a {} ", condition, nested.get_name());
+                assert!(script.contains(&entry));
+            }
+        }
+    }
+
+    #[test]
+    fn aur_packages_are_completed_from_the_aur() {
+        let script = generate(Shell::Bash, taur());
+        for (name, aur) in [("clone", true), ("vote", true), ("pull", false)] {
+            let branch = branch(&script, &format!("\n        {})\n", name));
+            assert_eq!(branch.contains("__complete --aur"), aur, "{}", name);
+            assert!(branch.contains("__complete"), "{}", name);
+        }
+    }
+}
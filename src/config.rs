@@ -0,0 +1,335 @@
+// config.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::output::{ColorChoice, Theme};
+use crate::remote::CloneScheme;
+
+/// Names of all settings, keys inside tables are joined with a dot
+pub const KEYS: [&str; 19] = [
+    "default_command",
+    "repos",
+    "jobs",
+    "color",
+    "post_pull_hook",
+    "clone_scheme",
+    "ssh_key",
+    "aur_session",
+    "chroot",
+    "makepkg_path",
+    "pacman_path",
+    "output_dir",
+    "recursive_scan",
+    "notify",
+    "colors.header",
+    "colors.package_name",
+    "colors.commit",
+    "colors.warning",
+    "colors.success",
+];
+
+/// Settings read from `config.toml` inside the taur config directory
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Command (with options) run when taur is called without a subcommand
+    pub default_command: Option<String>,
+    /// Directory of the repositories, used instead of $AURDEST or the default one
+    pub repos: Option<PathBuf>,
+    /// Number of repositories cloned or pulled at the same time
+    pub jobs: Option<usize>,
+    /// When to use colors (`auto`, `always` or `never`)
+    pub color: Option<ColorChoice>,
+    /// Command run after each successful pull
+    pub post_pull_hook: Option<String>,
+    /// URL scheme for cloning (`https` or `ssh`)
+    pub clone_scheme: CloneScheme,
+    /// SSH private key used instead of the ssh-agent
+    pub ssh_key: Option<PathBuf>,
+    /// Value of the `AURSID` cookie used for voting and flagging
+    pub aur_session: Option<String>,
+    /// Chroot used by `--chroot` of build, install and upgrade
+    pub chroot: Option<PathBuf>,
+    /// makepkg used for building instead of the one on the `PATH`
+    pub makepkg_path: Option<PathBuf>,
+    /// pacman used for installing and listing packages instead of the one on the `PATH`
+    pub pacman_path: Option<PathBuf>,
+    /// Directory built packages are put into instead of their repository
+    pub output_dir: Option<PathBuf>,
+    /// Look for repositories in subdirectories of the repos directory in fetch and pull
+    pub recursive_scan: bool,
+    /// Send a desktop notification when fetch finds upstream changes
+    pub notify: bool,
+    pub colors: ColorConfig,
+    /// Settings of single packages, by repository name
+    pub packages: BTreeMap<String, PackageConfig>,
+    /// Repos directories selectable with `--workspace`, by name
+    pub workspaces: BTreeMap<String, PathBuf>,
+}
+
+/// Settings of a single package from its `[packages.<name>]` table
+///
+/// Unset settings keep the behavior taur has for every other package.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageConfig {
+    /// Remote fetched and pulled from instead of `origin`
+    pub remote: Option<String>,
+    /// Branch of the remote followed instead of the one the checked out branch tracks
+    pub branch: Option<String>,
+    /// Leave the package out of fetch and upgrade
+    pub ignore: bool,
+    /// Arguments passed to makepkg in addition to taur's own when building the package
+    pub makepkg_args: Vec<String>,
+}
+
+/// Colors of the output roles, given as color names or ANSI color numbers
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    pub header: Option<String>,
+    pub package_name: Option<String>,
+    pub commit: Option<String>,
+    pub warning: Option<String>,
+    pub success: Option<String>,
+}
+
+impl Config {
+    pub fn path(proj_dirs: &ProjectDirs) -> PathBuf {
+        proj_dirs.config_dir().join("config.toml")
+    }
+
+    /// Loads the config file, falling back to the defaults if it does not exist
+    pub fn load(proj_dirs: &ProjectDirs) -> Result<Config, Error> {
+        let path = Config::path(proj_dirs);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid config file {:?}: {}", path, e),
+            )
+        })
+    }
+
+    /// Returns the settings of the given package, with the defaults for those it doesn't set
+    pub fn package(&self, name: &str) -> PackageConfig {
+        self.packages.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the repos directory of the given workspace
+    pub fn workspace(&self, name: &str) -> Result<PathBuf, Error> {
+        self.workspaces.get(name).cloned().ok_or_else(|| {
+            let known: Vec<&str> = self.workspaces.keys().map(String::as_str).collect();
+            Error::new(
+                ErrorKind::NotFound,
+                if known.is_empty() {
+                    format!("Unknown workspace '{}' (none configured)", name)
+                } else {
+                    format!("Unknown workspace '{}' (known: {})", name, known.join(", "))
+                },
+            )
+        })
+    }
+
+    /// Returns the extra makepkg arguments of all packages that have some
+    pub fn makepkg_args(&self) -> BTreeMap<String, Vec<String>> {
+        self.packages
+            .iter()
+            .filter(|(_, package)| !package.makepkg_args.is_empty())
+            .map(|(name, package)| (name.clone(), package.makepkg_args.clone()))
+            .collect()
+    }
+
+    /// Returns the effective value of a setting (including defaults), or None if it is unset
+    pub fn get(&self, key: &str) -> Result<Option<Value>, Error> {
+        check_key(key)?;
+
+        let table = Table::try_from(self).map_err(Error::other)?;
+        let mut value = Some(&Value::Table(table));
+        for part in key.split('.') {
+            value = value.and_then(|v| v.get(part));
+        }
+
+        Ok(value.cloned())
+    }
+
+    /// Changes a setting in the config file, creating the file if it does not exist
+    ///
+    /// The value is taken as TOML (e.g. `true` or `3`) if that fits the setting, as a string
+    /// otherwise. The changed config is validated before it is written.
+    pub fn set(proj_dirs: &ProjectDirs, key: &str, value: &str) -> Result<(), Error> {
+        check_key(key)?;
+
+        let path = Config::path(proj_dirs);
+        let mut table: Table = if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            content.parse().map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid config file {:?}: {}", path, e),
+                )
+            })?
+        } else {
+            Table::new()
+        };
+
+        let parsed = format!("value = {}", value)
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut t| t.remove("value"));
+        let candidates = parsed
+            .into_iter()
+            .chain(std::iter::once(Value::String(value.to_string())));
+
+        let mut error = None;
+        for candidate in candidates {
+            set_value(&mut table, key, candidate);
+            match validate(&table) {
+                Ok(()) => {
+                    std::fs::create_dir_all(proj_dirs.config_dir())?;
+                    let content = toml::to_string_pretty(&table).map_err(Error::other)?;
+                    return std::fs::write(&path, content);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+
+        Err(error.unwrap_or_else(|| Error::other("No value given")))
+    }
+}
+
+fn check_key(key: &str) -> Result<(), Error> {
+    if KEYS.contains(&key) {
+        return Ok(());
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        format!("Unknown setting '{}' (known: {})", key, KEYS.join(", ")),
+    ))
+}
+
+/// Sets a dotted key inside the table, creating intermediate tables as needed
+fn set_value(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((outer, inner)) => {
+            let entry = table
+                .entry(outer)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(nested) = entry {
+                set_value(nested, inner, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Checks that the table is a valid config, including the colors
+fn validate(table: &Table) -> Result<(), Error> {
+    let config: Config = table.clone().try_into().map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            e.to_string().trim().replace('\n', " "),
+        )
+    })?;
+    Theme::from_config(&config.colors)?;
+    crate::default_command_of(&config).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Repository;
+
+    use super::*;
+    use crate::remote::Upstream;
+
+    const CONFIG: &str = r#"
+        jobs = 4
+
+        [packages.foo]
+        remote = "mirror"
+        branch = "stable"
+        makepkg_args = ["--nocheck"]
+
+        [packages.bar]
+        ignore = true
+    "#;
+
+    #[test]
+    fn package_settings_override_defaults() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        let foo = Upstream::of(&repo, &config.package("foo"));
+        assert_eq!(
+            (foo.remote.as_str(), foo.branch.as_str()),
+            ("mirror", "stable")
+        );
+        assert_eq!(foo.refname(), "refs/remotes/mirror/stable");
+        assert!(!config.package("foo").ignore);
+
+        let bar = Upstream::of(&repo, &config.package("bar"));
+        assert_eq!(
+            (bar.remote.as_str(), bar.branch.as_str()),
+            ("origin", "master")
+        );
+        assert!(config.package("bar").ignore);
+        assert!(!Upstream::is_overridden(&config.package("bar")));
+
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(
+            config.makepkg_args(),
+            BTreeMap::from([(String::from("foo"), vec![String::from("--nocheck")])])
+        );
+    }
+
+    #[test]
+    fn unknown_package_gets_defaults() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let baz = config.package("baz");
+        assert_eq!(baz.remote, None);
+        assert_eq!(baz.branch, None);
+        assert!(!baz.ignore);
+        assert!(baz.makepkg_args.is_empty());
+    }
+
+    #[test]
+    fn unknown_package_setting_is_rejected() {
+        let result = toml::from_str::<Config>("[packages.foo]\nremotes = \"mirror\"\n");
+        assert!(result.is_err());
+    }
+}
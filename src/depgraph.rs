@@ -0,0 +1,212 @@
+// depgraph.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::rpc;
+use crate::srcinfo::{strip_version, Srcinfo};
+
+/// Where a dependency can be installed from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Repo,
+    Aur,
+    Missing,
+}
+
+/// Dependency graph of an AUR package
+pub struct DepTree {
+    pub root: String,
+    /// Dependencies (depends, makedepends and checkdepends) of every AUR package in the graph
+    pub aur: BTreeMap<String, Vec<String>>,
+    /// Dependencies that can be installed from the official repositories
+    pub repo: BTreeSet<String>,
+    /// Package base (the name of the AUR repository) of every AUR package in the graph
+    pub package_bases: BTreeMap<String, String>,
+}
+
+impl DepTree {
+    pub fn source(&self, name: &str) -> Source {
+        if self.aur.contains_key(name) {
+            Source::Aur
+        } else if self.repo.contains(name) {
+            Source::Repo
+        } else {
+            Source::Missing
+        }
+    }
+
+    /// Returns every package in the graph except the root, sorted by name
+    pub fn dependencies(&self) -> BTreeSet<&str> {
+        self.aur
+            .values()
+            .flatten()
+            .map(String::as_str)
+            .filter(|&name| name != self.root)
+            .collect()
+    }
+}
+
+/// Resolves all dependencies of the given AUR package, following AUR dependencies recursively
+///
+/// Dependencies that are neither in AUR nor in the official repositories (as known to pacman)
+/// are reported as missing. Dependencies satisfied by a `provides` of another AUR package are
+/// not resolved.
+pub async fn resolve(root: &str) -> Result<DepTree, Box<dyn std::error::Error>> {
+    let raur = raur::Handle::new();
+
+    let mut aur: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut package_bases: BTreeMap<String, String> = BTreeMap::new();
+    let mut queried: HashSet<String> = HashSet::new();
+    let mut pending = vec![root.to_string()];
+
+    while !pending.is_empty() {
+        queried.extend(pending.iter().cloned());
+        for pkg in rpc::info(&raur, &pending).await?.found {
+            let deps = pkg
+                .depends
+                .iter()
+                .chain(pkg.make_depends.iter())
+                .chain(pkg.check_depends.iter())
+                .map(|dep| strip_version(dep).to_string())
+                .collect();
+            package_bases.insert(pkg.name.clone(), pkg.package_base);
+            aur.insert(pkg.name, deps);
+        }
+
+        let mut next: Vec<String> = aur
+            .values()
+            .flatten()
+            .filter(|dep| !queried.contains(*dep))
+            .cloned()
+            .collect();
+        next.sort_unstable();
+        next.dedup();
+        pending = next;
+    }
+
+    if !aur.contains_key(root) {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("Package '{}' not found", root),
+        )));
+    }
+
+    let others: BTreeSet<&str> = aur
+        .values()
+        .flatten()
+        .map(String::as_str)
+        .filter(|dep| !aur.contains_key(*dep))
+        .collect();
+    let repo = in_repos(&others);
+
+    Ok(DepTree {
+        root: root.to_string(),
+        aur,
+        repo,
+        package_bases,
+    })
+}
+
+/// Returns those of the given names that pacman can install from the sync databases
+fn in_repos(names: &BTreeSet<&str>) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+
+    let sync_packages: HashSet<String> = match Command::new("pacman").arg("-Slq").output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => return found,
+    };
+
+    for &name in names {
+        // Names that aren't package names may still be provided by a repo package (e.g. `sh`)
+        let known = sync_packages.contains(name)
+            || Command::new("pacman")
+                .args(["-Sp", "--print-format", "%n", name])
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false);
+        if known {
+            found.insert(name.to_string());
+        }
+    }
+
+    found
+}
+
+/// Orders the given packages so that each one comes after the packages it depends on
+///
+/// Only dependencies between the given packages are considered, everything else is expected
+/// to be installed from the official repositories or to already be present on the system.
+pub fn build_order(packages: &BTreeMap<String, Srcinfo>) -> Result<Vec<String>, Error> {
+    // Map every package name (including split packages and provides) to its repository
+    let mut providers: HashMap<&str, &str> = HashMap::new();
+    for (name, srcinfo) in packages {
+        providers.insert(name, name);
+        for provided in srcinfo.pkgnames.iter().chain(srcinfo.provides.iter()) {
+            providers.entry(provided).or_insert(name);
+        }
+    }
+
+    let mut dependencies: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (name, srcinfo) in packages {
+        let deps = dependencies.entry(name).or_default();
+        for dep in srcinfo.all_depends() {
+            if let Some(&provider) = providers.get(dep.as_str()) {
+                if provider != name {
+                    deps.insert(provider);
+                }
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(packages.len());
+    while !dependencies.is_empty() {
+        let ready: Vec<&str> = dependencies
+            .iter()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(&name, _)| name)
+            .collect();
+
+        if ready.is_empty() {
+            let cycle: Vec<&str> = dependencies.keys().copied().collect();
+            return Err(Error::other(format!(
+                "Dependency cycle between packages: {}",
+                cycle.join(", ")
+            )));
+        }
+
+        for name in ready {
+            dependencies.remove(name);
+            for deps in dependencies.values_mut() {
+                deps.remove(name);
+            }
+            order.push(name.to_string());
+        }
+    }
+
+    Ok(order)
+}
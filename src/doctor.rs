@@ -0,0 +1,224 @@
+// doctor.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use directories::ProjectDirs;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::output::Theme;
+use crate::remote::{CloneScheme, AUR_HOST};
+
+/// How long the AUR gets to answer before it counts as unreachable
+const AUR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of a single check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    /// Something that only some commands or options need
+    Warn,
+    /// Something taur can't work without
+    Fail,
+}
+
+/// Result of checking one part of the environment
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, detail: String) -> Check {
+        Check {
+            name: name.to_string(),
+            status: Status::Pass,
+            detail,
+            remedy: None,
+        }
+    }
+
+    fn problem(name: &str, status: Status, detail: String, remedy: &str) -> Check {
+        Check {
+            name: name.to_string(),
+            status,
+            detail,
+            remedy: Some(remedy.to_string()),
+        }
+    }
+}
+
+/// Checks that the config file (if there is one) can be read, returning it if so
+pub fn config(proj_dirs: &ProjectDirs) -> (Check, Option<Config>) {
+    let path = Config::path(proj_dirs);
+    let remedy = "Fix the file, or change settings with 'taur config set' (which validates them)";
+
+    let config = match Config::load(proj_dirs) {
+        Ok(config) => config,
+        Err(e) => {
+            return (
+                Check::problem("config", Status::Fail, e.to_string(), remedy),
+                None,
+            )
+        }
+    };
+    if let Err(e) = Theme::from_config(&config.colors) {
+        return (
+            Check::problem("config", Status::Fail, e.to_string(), remedy),
+            None,
+        );
+    }
+    if let Err(e) = crate::default_command_of(&config) {
+        return (
+            Check::problem("config", Status::Fail, e.to_string(), remedy),
+            None,
+        );
+    }
+
+    let detail = match path.exists() {
+        true => format!("{} is valid", path.display()),
+        false => format!("{} does not exist, using the defaults", path.display()),
+    };
+    (Check::pass("config", detail), Some(config))
+}
+
+/// Checks that the repos directory exists and new repositories can be cloned into it
+pub fn repos_dir(path: &Path) -> Check {
+    if !path.exists() {
+        return Check::problem(
+            "repos directory",
+            Status::Warn,
+            format!("{} does not exist yet", path.display()),
+            "It is created by the first clone, or pass another directory as the first argument",
+        );
+    }
+    if !path.is_dir() {
+        return Check::problem(
+            "repos directory",
+            Status::Fail,
+            format!("{} is not a directory", path.display()),
+            "Remove it or pass another directory as the first argument (or set AURDEST)",
+        );
+    }
+
+    // Permissions don't tell about read-only mounts or ACLs, so actually try to write
+    let probe = path.join(".taur-write-test");
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => Check::pass("repos directory", format!("{} is writable", path.display())),
+        Err(e) => Check::problem(
+            "repos directory",
+            Status::Fail,
+            format!("Unable to write into {}: {}", path.display(), e),
+            "Make it writable for your user, or pass another directory as the first argument",
+        ),
+    }
+}
+
+/// Checks that a program can be run and reports its version
+///
+/// A missing program is a failure if `required` is set, a warning otherwise.
+pub fn program(program: &Path, name: &str, required: bool, remedy: &str) -> Check {
+    let status = if required { Status::Fail } else { Status::Warn };
+
+    let output = match Command::new(program).arg("--version").output() {
+        Ok(output) => output,
+        Err(e) => {
+            let detail = format!("Unable to run {}: {}", program.display(), e);
+            return Check::problem(name, status, detail, remedy);
+        }
+    };
+    if !output.status.success() {
+        let detail = format!("{} --version failed ({})", program.display(), output.status);
+        return Check::problem(name, status, detail, remedy);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = match version(&stdout) {
+        Some(version) => format!("{} {}", program.display(), version),
+        None => program.display().to_string(),
+    };
+    Check::pass(name, detail)
+}
+
+/// Picks the version number out of the output of `--version`, e.g. `6.1.0` out of
+/// `makepkg (pacman) 6.1.0` or `Pacman v6.1.0 - libalpm v14.0.0`
+fn version(output: &str) -> Option<&str> {
+    output.split_whitespace().find_map(|word| {
+        let number = word.strip_prefix('v').unwrap_or(word);
+        number
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then_some(number)
+    })
+}
+
+/// Checks that the AUR answers RPC requests
+pub async fn aur(client: &reqwest::Client) -> Check {
+    let url = format!("https://{}/rpc/?v=5&type=info&arg[]=taur", AUR_HOST);
+    let response = client.get(&url).timeout(AUR_TIMEOUT).send().await;
+
+    match response.and_then(|response| response.error_for_status()) {
+        Ok(_) => Check::pass("AUR", format!("{} is reachable", AUR_HOST)),
+        Err(e) => Check::problem(
+            "AUR",
+            Status::Fail,
+            format!("Unable to reach {}: {}", AUR_HOST, e),
+            "Check your network connection and proxy settings (HTTPS_PROXY)",
+        ),
+    }
+}
+
+/// Checks that there is a key to authenticate with, if cloning over SSH is configured
+pub fn ssh(config: &Config) -> Option<Check> {
+    if config.clone_scheme != CloneScheme::Ssh {
+        return None;
+    }
+
+    let check = match &config.ssh_key {
+        Some(key) if key.is_file() => {
+            Check::pass("SSH", format!("Using the key {}", key.display()))
+        }
+        Some(key) => Check::problem(
+            "SSH",
+            Status::Fail,
+            format!("The configured ssh_key {} does not exist", key.display()),
+            "Fix the path with 'taur config set ssh_key PATH'",
+        ),
+        None if std::env::var_os("SSH_AUTH_SOCK").is_some() => {
+            Check::pass("SSH", String::from("Using the keys of the ssh-agent"))
+        }
+        None => Check::problem(
+            "SSH",
+            Status::Fail,
+            String::from(
+                "clone_scheme is ssh, but no ssh-agent is running (SSH_AUTH_SOCK is unset)",
+            ),
+            "Start an ssh-agent and add your AUR key with ssh-add, or set ssh_key",
+        ),
+    };
+    Some(check)
+}
@@ -0,0 +1,101 @@
+// error.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// Errors of requests to the AUR, classified by how taur should react to them
+#[derive(Debug)]
+pub enum TaurError {
+    /// The requested resource does not exist (HTTP 404), retrying won't help
+    NotFound,
+    /// The AUR is overloaded or failing (HTTP 429 or 5xx), worth retrying later
+    ServerError(u16),
+    /// The response could not be understood
+    Parse(String),
+    /// The AUR could not be reached at all
+    Network(String),
+    /// The AUR rejected the query itself (e.g. too many results)
+    Query(String),
+}
+
+impl TaurError {
+    /// Returns true if the same request may succeed when repeated
+    pub fn is_transient(&self) -> bool {
+        matches!(self, TaurError::ServerError(_) | TaurError::Network(_))
+    }
+}
+
+impl From<raur::Error> for TaurError {
+    fn from(e: raur::Error) -> Self {
+        let e = match e {
+            raur::Error::Aur(message) => return TaurError::Query(message),
+            raur::Error::Reqwest(e) => e,
+        };
+
+        match e.status() {
+            Some(status) if status.as_u16() == 404 => TaurError::NotFound,
+            Some(status) if status.as_u16() == 429 || status.is_server_error() => {
+                TaurError::ServerError(status.as_u16())
+            }
+            _ if e.is_decode() => TaurError::Parse(e.to_string()),
+            _ => TaurError::Network(e.to_string()),
+        }
+    }
+}
+
+impl Display for TaurError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            TaurError::NotFound => write!(f, "The AUR could not find what was asked for"),
+            TaurError::ServerError(status) => write!(
+                f,
+                "The AUR is unavailable right now (HTTP {}), please try again later",
+                status
+            ),
+            TaurError::Parse(e) => write!(f, "Unexpected response from the AUR: {}", e),
+            TaurError::Network(e) => write!(f, "Unable to reach the AUR: {}", e),
+            TaurError::Query(e) => write!(f, "The AUR rejected the query: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TaurError {}
+
+/// How often a transient failure is retried before giving up
+const RETRIES: u32 = 2;
+
+/// Runs an AUR request, repeating it with a growing delay if it fails transiently
+pub async fn retry<T, F, Fut>(mut request: F) -> Result<T, TaurError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, raur::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await.map_err(TaurError::from) {
+            Err(e) if e.is_transient() && attempt < RETRIES => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+            }
+            result => return result,
+        }
+    }
+}
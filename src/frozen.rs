@@ -0,0 +1,75 @@
+// frozen.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// Packages whose updates are temporarily ignored by fetch and upgrade
+///
+/// They are kept in `frozen.toml` inside the data directory instead of the config, as they
+/// are expected to change often.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Frozen {
+    packages: BTreeSet<String>,
+}
+
+impl Frozen {
+    fn path(proj_dirs: &ProjectDirs) -> PathBuf {
+        proj_dirs.data_dir().join("frozen.toml")
+    }
+
+    pub fn load(proj_dirs: &ProjectDirs) -> Result<Frozen, Error> {
+        let path = Frozen::path(proj_dirs);
+        if !path.exists() {
+            return Ok(Frozen::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid lockfile {:?}: {}", path, e),
+            )
+        })
+    }
+
+    pub fn save(&self, proj_dirs: &ProjectDirs) -> Result<(), Error> {
+        let content = toml::to_string(self).map_err(Error::other)?;
+        std::fs::create_dir_all(proj_dirs.data_dir())?;
+        std::fs::write(Frozen::path(proj_dirs), content)
+    }
+
+    pub fn contains(&self, package_name: &str) -> bool {
+        self.packages.contains(package_name)
+    }
+
+    /// Returns false if the package was already frozen
+    pub fn freeze(&mut self, package_name: &str) -> bool {
+        self.packages.insert(package_name.to_string())
+    }
+
+    /// Returns false if the package was not frozen
+    pub fn thaw(&mut self, package_name: &str) -> bool {
+        self.packages.remove(package_name)
+    }
+}
@@ -0,0 +1,145 @@
+// ignore.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Glob patterns from a `.taurignore` file in the repos directory
+///
+/// Each line is a pattern matched against the directory names of the packages, using the
+/// gitignore syntax: `*` and `?` are wildcards, `[...]` matches a character class, a leading
+/// `!` re-includes packages excluded by an earlier pattern and lines starting with `#` are
+/// comments. The last matching pattern wins.
+#[derive(Debug, Default)]
+pub struct TaurIgnore {
+    patterns: Vec<(Vec<char>, bool)>,
+}
+
+impl TaurIgnore {
+    pub fn load(repo_path: &Path) -> Result<TaurIgnore, Error> {
+        let path = repo_path.join(".taurignore");
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(TaurIgnore::default()),
+            Err(e) => return Err(Error::new(e.kind(), format!("{:?}: {}", path, e))),
+        };
+
+        let patterns = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_prefix('!') {
+                Some(pattern) => (pattern, false),
+                None => (line, true),
+            })
+            .map(|(pattern, ignore)| {
+                // Only directories are matched anyway
+                let pattern = pattern.trim_end_matches('/');
+                (pattern.chars().collect(), ignore)
+            })
+            .collect();
+
+        Ok(TaurIgnore { patterns })
+    }
+
+    /// Adds a pattern matching only the given package to the `.taurignore` file
+    pub fn add(repo_path: &Path, package_name: &str) -> Result<(), Error> {
+        let path = repo_path.join(".taurignore");
+        let mut content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::new(e.kind(), format!("{:?}: {}", path, e))),
+        };
+
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        for c in package_name.chars() {
+            if matches!(c, '*' | '?' | '[' | '\\') {
+                content.push('\\');
+            }
+            content.push(c);
+        }
+        content.push('\n');
+
+        std::fs::write(&path, content)
+            .map_err(|e| Error::new(e.kind(), format!("{:?}: {}", path, e)))
+    }
+
+    pub fn is_ignored(&self, package_name: &str) -> bool {
+        let name: Vec<char> = package_name.chars().collect();
+        self.patterns
+            .iter()
+            .rev()
+            .find(|(pattern, _)| glob_match(pattern, &name))
+            .is_some_and(|(_, ignore)| *ignore)
+    }
+}
+
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some('[') => match (class_match(&pattern[1..], name.first()), name.split_first()) {
+            (Some((true, rest)), Some((_, name))) => glob_match(rest, name),
+            (Some((false, _)), _) | (Some(_), None) => false,
+            // An unclosed bracket is taken literally
+            (None, _) => name.first() == Some(&'[') && glob_match(&pattern[1..], &name[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &name[1..])
+        }
+        Some(c) => name.first() == Some(c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Matches a character against the class starting after a `[`
+///
+/// Returns whether it matched and the rest of the pattern after the closing `]`, or None if
+/// the class is not closed.
+fn class_match<'a>(class: &'a [char], c: Option<&char>) -> Option<(bool, &'a [char])> {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    // A `]` right at the start is part of the class
+    let end = class.iter().skip(1).position(|&c| c == ']')? + 1;
+    let (members, rest) = (&class[..end], &class[end + 1..]);
+
+    let c = match c {
+        Some(c) => *c,
+        None => return Some((false, rest)),
+    };
+
+    let mut matched = false;
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            matched |= members[i] <= c && c <= members[i + 2];
+            i += 3;
+        } else {
+            matched |= members[i] == c;
+            i += 1;
+        }
+    }
+
+    Some((matched != negated, rest))
+}
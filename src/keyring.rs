@@ -0,0 +1,79 @@
+// keyring.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, Write};
+use std::process::{Command, Stdio};
+
+/// Attributes identifying the AUR session in the keyring
+const ATTRIBUTES: [&str; 4] = ["service", "taur", "key", "aur_session"];
+
+/// Returns the AUR session stored in the system keyring, if there is one
+///
+/// The keyring is accessed with `secret-tool` from libsecret, so any Secret Service provider
+/// (like GNOME Keyring or KeePassXC) works. Without `secret-tool` or a running provider, this
+/// behaves as if nothing was stored.
+pub fn lookup() -> Option<String> {
+    let output = Command::new("secret-tool")
+        .arg("lookup")
+        .args(ATTRIBUTES)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    let secret = String::from_utf8(output.stdout).ok()?;
+    let secret = secret.trim();
+    if !output.status.success() || secret.is_empty() {
+        return None;
+    }
+
+    Some(secret.to_string())
+}
+
+/// Stores the AUR session in the system keyring, replacing a stored one
+///
+/// The secret is passed on stdin, so it never shows up in the process list.
+pub fn store(secret: &str) -> Result<(), Error> {
+    let mut child = Command::new("secret-tool")
+        .arg("store")
+        .arg("--label=taur AUR session")
+        .args(ATTRIBUTES)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!(
+                    "Unable to run secret-tool (part of libsecret): {}, set aur_session in \
+                     the config instead",
+                    e
+                ),
+            )
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(secret.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::other(format!("secret-tool exited with {}", status)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+// lastfetch.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// Time of the last successful fetch of each repository
+///
+/// Kept in `last-fetch.toml` inside the data directory as seconds since the Unix epoch.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LastFetch {
+    repos: BTreeMap<String, u64>,
+}
+
+impl LastFetch {
+    fn path(proj_dirs: &ProjectDirs) -> PathBuf {
+        proj_dirs.data_dir().join("last-fetch.toml")
+    }
+
+    pub fn load(proj_dirs: &ProjectDirs) -> Result<LastFetch, Error> {
+        let path = LastFetch::path(proj_dirs);
+        if !path.exists() {
+            return Ok(LastFetch::default());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid state file {:?}: {}", path, e),
+            )
+        })
+    }
+
+    pub fn save(&self, proj_dirs: &ProjectDirs) -> Result<(), Error> {
+        let content = toml::to_string(self).map_err(Error::other)?;
+        std::fs::create_dir_all(proj_dirs.data_dir())?;
+        std::fs::write(LastFetch::path(proj_dirs), content)
+    }
+
+    /// Returns how many seconds ago the repository was fetched, if it ever was
+    pub fn age(&self, package_name: &str) -> Option<u64> {
+        self.repos
+            .get(package_name)
+            .map(|&time| now().saturating_sub(time))
+    }
+
+    /// Records that the repository was fetched just now
+    pub fn touch(&mut self, package_name: &str) {
+        self.repos.insert(package_name.to_string(), now());
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,52 @@
+// lock.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::fs::{File, TryLockError};
+use std::io::{Error, ErrorKind};
+
+use directories::ProjectDirs;
+
+/// Advisory lock keeping two taur processes from changing the repositories at the same time
+///
+/// The lock is held until the value is dropped. As it is tied to the open file, the operating
+/// system also releases it when taur exits in any other way, e.g. after a panic.
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Takes the lock on `taur.lock` inside the data directory, failing if it is already held
+    pub fn acquire(proj_dirs: &ProjectDirs) -> Result<Lock, Error> {
+        std::fs::create_dir_all(proj_dirs.data_dir())?;
+        let path = proj_dirs.data_dir().join("taur.lock");
+        let file = File::create(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Lock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(Error::new(
+                ErrorKind::WouldBlock,
+                format!(
+                    "Another taur is running (lock {:?} is held), try again when it finished",
+                    path
+                ),
+            )),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
@@ -17,404 +17,6781 @@
 // * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
 // *************************************************************************
 
+mod audit;
+mod aurweb;
+mod build;
+mod checksums;
+mod comments;
+mod completions;
+mod config;
+mod depgraph;
+mod doctor;
+mod error;
+mod frozen;
+mod ignore;
+mod keyring;
+mod lastfetch;
+mod lock;
+mod notify;
+mod output;
+mod packagedb;
+mod progress;
+mod remote;
+mod rpc;
+mod signature;
+mod srcinfo;
+mod timer;
+mod tui;
+mod version;
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::ffi::OsString;
 use std::fmt::Display;
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Write};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::Parser;
+use clap::builder::RangedU64ValueParser;
+use clap::{CommandFactory, Parser};
 use directories::ProjectDirs;
-use git2::Repository;
-use raur::Raur;
+use futures::StreamExt;
+use git2::{Branch, ErrorClass, ErrorCode, Repository};
+use raur::{Raur, SearchBy};
+use regex_automata::meta::Regex;
+use serde::Serialize;
+use termion::input::TermRead;
 use termion::{color, style};
 use tokio::task;
 
+use audit::Finding;
+use aurweb::AurSession;
+use build::{BuildOptions, Tools};
+use config::{Config, PackageConfig};
+use depgraph::{DepTree, Source};
+use frozen::Frozen;
+use ignore::TaurIgnore;
+use lastfetch::LastFetch;
+use lock::Lock;
+use output::{ColorChoice, Formatter, OutputFormat, StatusFormat, Theme, WaybarStatus};
+use progress::{Progress, RepoState, StatusTable};
+use remote::{CloneScheme, RemoteAuth, Upstream};
+use signature::SignatureStatus;
+use srcinfo::Srcinfo;
+
 #[derive(Debug, Parser)]
 #[command(name = "taur", about = "Tiny AUR helper")]
 struct Args {
-    /// Local repo storage path (defaults to $HOME/.local/share/taur/repos)
-    #[arg()]
+    /// Local repo storage path (defaults to the `repos` setting, $AURDEST or
+    /// $HOME/.local/share/taur/repos)
+    #[arg(long, global = true, value_name = "PATH")]
     repos: Option<PathBuf>,
+    /// Use the repositories of a workspace from the `[workspaces]` config table
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "repos")]
+    workspace: Option<String>,
+    /// The repos directory as first argument, from before there was --repos
+    #[arg(hide = true)]
+    legacy_repos: Option<PathBuf>,
+    /// Output format (defaults to pretty)
+    #[arg(long, value_enum, global = true)]
+    output: Option<OutputFormat>,
+    /// Shorthand for `--output json`
+    #[arg(long, global = true, conflicts_with = "output")]
+    json: bool,
+    /// When to use colors in pretty output (defaults to the `color` setting or auto)
+    #[arg(long, value_enum, global = true)]
+    color: Option<ColorChoice>,
+    /// Answer yes to all confirmation prompts (also passed on to makepkg and pacman)
+    #[arg(short = 'y', long, global = true)]
+    no_confirm: bool,
+    /// Only report what commands changing repositories or settings would do
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// makepkg to build with (defaults to `makepkg_path` from the config or makepkg on the PATH)
+    #[arg(long, global = true, value_name = "PATH")]
+    makepkg_path: Option<PathBuf>,
+    /// pacman to install with (defaults to `pacman_path` from the config or pacman on the PATH)
+    #[arg(long, global = true, value_name = "PATH")]
+    pacman_path: Option<PathBuf>,
+    /// Put built packages into this directory instead of their repository (defaults to
+    /// `output_dir` from the config)
+    #[arg(long, global = true, value_name = "PATH")]
+    output_dir: Option<PathBuf>,
+    /// Use a separate set of repositories, config and data with the given name
+    #[arg(long, global = true, value_parser = parse_profile)]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Option<Command>,
 }
 
+#[derive(Debug, clap::Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of a setting
+    #[command(name = "get")]
+    Get { key: String },
+    /// Change a setting
+    #[command(name = "set")]
+    Set { key: String, value: String },
+    /// Print the path of the config file
+    #[command(name = "path")]
+    Path,
+}
+
 #[derive(Debug, Parser)]
 enum Command {
     /// Clone a repository from AUR
     #[command(name = "clone")]
-    Clone { package_name: String },
+    Clone {
+        #[arg(required = true, value_name = completions::AUR_PACKAGE)]
+        package_names: Vec<String>,
+        /// Remove existing repository directories and clone them again
+        #[arg(long)]
+        force: bool,
+        /// Also clone the AUR dependencies of the package
+        #[arg(short, long)]
+        recursive: bool,
+        /// Check out and track this branch instead of the remote's default branch
+        #[arg(long, value_name = "NAME")]
+        branch: Option<String>,
+        /// Reset the checked out branch to the last commit of this version (like `1.2-3` or
+        /// just the pkgver `1.2`)
+        #[arg(long, value_name = "VER")]
+        at_version: Option<String>,
+        /// Number of dependencies cloned at the same time (defaults to the `jobs` setting or 4)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Continue cloning the remaining packages and dependencies after a failure
+        #[arg(long)]
+        keep_going: bool,
+    },
     /// Fetch and print new commits for all repositories
     #[command(name = "fetch")]
-    Fetch,
+    Fetch {
+        /// Don't show the live status table while fetching
+        #[arg(short, long)]
+        quiet: bool,
+        /// List at most this many incoming commits per repository
+        #[arg(long, value_name = "N", visible_alias = "depth")]
+        max_commits: Option<usize>,
+        /// Exit with a non-zero status if any repository could not be checked
+        #[arg(long)]
+        exit_code: bool,
+        /// Only warn about repositories that could not be checked and always exit with status
+        /// zero, e.g. for cron jobs that shouldn't fail on a flaky mirror
+        #[arg(long, conflicts_with = "exit_code")]
+        ignore_errors: bool,
+        /// Don't fetch repositories that were fetched less than this many seconds ago, only
+        /// compare them with the already fetched state
+        #[arg(long, value_name = "SECS")]
+        stale: Option<u64>,
+        /// Print only a compact status for status bars instead of the new commits
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        status_format: Option<StatusFormat>,
+        /// Check the GPG signatures of incoming commits against the local keyring
+        #[arg(long)]
+        verify_signatures: bool,
+        /// Always fetch, instead of only when a quick look at the remote shows new commits
+        #[arg(long)]
+        deep: bool,
+        /// Print how long fetching and checking took for each repository, slowest first
+        /// (not with --quiet)
+        #[arg(long, conflicts_with = "status_format")]
+        timings: bool,
+        /// Only print how many commits each repository is behind and ahead of upstream
+        #[arg(long, conflicts_with_all = ["max_commits", "verify_signatures", "status_format"])]
+        summary: bool,
+        /// Only show commits whose message matches this regular expression (repositories
+        /// without matching commits are left out)
+        #[arg(long, value_name = "PATTERN", value_parser = parse_regex, conflicts_with = "summary")]
+        grep: Option<Regex>,
+        /// Also look for repositories in subdirectories (defaults to `recursive_scan` from the
+        /// config)
+        #[arg(long)]
+        recursive_scan: bool,
+        /// Send a desktop notification if packages have upstream changes (defaults to
+        /// `notify` from the config)
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Print package names starting with the given prefix, for shell completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(default_value = "")]
+        current: String,
+        /// Complete AUR package names instead of cloned repositories
+        #[arg(long)]
+        aur: bool,
+    },
+    /// Print a completion script for bash, zsh or fish
+    ///
+    /// Install it where the shell looks for completions, e.g. `taur completions bash >
+    /// /usr/share/bash-completion/completions/taur`.
+    #[command(name = "completions")]
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// List all cloned repositories with their state
+    #[command(name = "list")]
+    List {
+        /// Order of the repositories
+        #[arg(long, value_enum, default_value_t = SortBy::Name)]
+        sort_by: SortBy,
+        /// Print one line per repository for scripts, ignoring --output
+        ///
+        /// Each line has the fields `<name> <behind> <ahead> <dirty> <head>`, separated by a
+        /// single space: the commits behind and ahead of upstream as of the last fetch, 1 if
+        /// tracked files were modified (0 otherwise), and the full id of the checked out commit.
+        /// This format is stable: new fields are only ever appended at the end of a line.
+        #[arg(long)]
+        porcelain: bool,
+        /// Fetch all repositories first, instead of showing the state of the last fetch
+        #[arg(long)]
+        fetch: bool,
+    },
+    /// Browse the repositories in a full-screen view, pulling, building or opening them
+    Tui,
+    /// Show details of AUR packages
+    #[command(name = "info")]
+    Info {
+        #[arg(required = true, value_name = completions::AUR_PACKAGE)]
+        package_names: Vec<String>,
+        /// Print the packages as returned by the AUR RPC interface
+        #[arg(long)]
+        raw_json: bool,
+        /// Only print the value of this field (like version or maintainer), may be repeated
+        #[arg(long = "field", value_name = "NAME", conflicts_with = "raw_json")]
+        fields: Vec<String>,
+    },
+    /// Clone (or pull) given packages, build them and install them with pacman
+    #[command(name = "install")]
+    Install {
+        #[arg(required = true, value_name = completions::AUR_PACKAGE)]
+        package_names: Vec<String>,
+        /// Build in a clean chroot with makechrootpkg (defaults to `chroot` from the config)
+        #[arg(long, value_name = "PATH", num_args = 0..=1)]
+        chroot: Option<Option<PathBuf>>,
+        /// Continue with the remaining packages after a failure and install the ones that were
+        /// built
+        #[arg(long)]
+        keep_going: bool,
+        /// Pull without showing the changes to PKGBUILD and install files and asking first
+        #[arg(long)]
+        skip_review: bool,
+    },
+    /// Search AUR, pick one of the results and clone it
+    #[command(name = "get")]
+    Get {
+        expression: String,
+        /// Also build and install the selected package
+        #[arg(long)]
+        install: bool,
+    },
     /// Search for packages in AUR
     #[command(name = "search")]
-    Search { expression: String },
-    /// Pull given package repositories (if no package is specified, all repositories are pulled)
+    Search {
+        expression: String,
+        /// Mark packages that are already cloned
+        #[arg(long)]
+        installed: bool,
+        /// Only show packages that are already cloned
+        #[arg(long)]
+        installed_only: bool,
+        /// Look up the package with exactly this name (exits non-zero if it doesn't exist)
+        #[arg(long)]
+        exact: bool,
+        /// Show at most this many packages per page
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        limit: Option<usize>,
+        /// Page of the results to show with --limit
+        #[arg(
+            long,
+            requires = "limit",
+            default_value_t = 1,
+            value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        page: usize,
+        /// Cut descriptions to fit into this many columns (defaults to the terminal width)
+        #[arg(long, value_name = "N", conflicts_with = "no_truncate")]
+        width: Option<usize>,
+        /// Print descriptions in full, even if they don't fit on the terminal
+        #[arg(long)]
+        no_truncate: bool,
+        /// Search the package database downloaded with `sync-db` instead of asking the AUR
+        #[arg(long)]
+        offline: bool,
+        /// Field of the packages the expression is matched against
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = SearchField::NameDesc,
+            conflicts_with_all = ["exact", "offline"]
+        )]
+        by: SearchField,
+        /// Pick packages from the numbered results and clone them
+        #[arg(long)]
+        select: bool,
+    },
+    /// Download the metadata of all AUR packages for `search --offline`
+    #[command(name = "sync-db")]
+    SyncDb {
+        /// Download the database even if the cached one is still current
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Pull given package repositories
     #[command(name = "pull")]
-    Pull { package_names: Vec<String> },
+    Pull {
+        package_names: Vec<String>,
+        /// Pull all repositories
+        #[arg(long, conflicts_with = "package_names")]
+        all: bool,
+        /// Refuse to pull commits without a good GPG signature from the local keyring
+        #[arg(long)]
+        require_signatures: bool,
+        /// Command to run after each successful pull (overrides `post_pull_hook` from the
+        /// config). It runs inside the repository and gets the package name and the new HEAD
+        /// as arguments and as TAUR_PACKAGE and TAUR_HEAD environment variables.
+        #[arg(long, value_name = "CMD")]
+        hook: Option<String>,
+        /// Number of repositories pulled at the same time (defaults to the `jobs` setting or 4)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Continue pulling the remaining repositories after a failure
+        #[arg(long)]
+        keep_going: bool,
+        /// Also look for repositories in subdirectories with --all (defaults to
+        /// `recursive_scan` from the config)
+        #[arg(long)]
+        recursive_scan: bool,
+        /// Pull without showing the changes to PKGBUILD and install files and asking first
+        #[arg(long)]
+        skip_review: bool,
+    },
+    /// Print cloned packages in build order, each after the packages it depends on
+    #[command(name = "order")]
+    Order {
+        package_names: Vec<String>,
+        /// Order all repositories
+        #[arg(long, conflicts_with = "package_names")]
+        all: bool,
+    },
+    /// Build given packages with makepkg and print the built package files
+    #[command(name = "build")]
+    Build {
+        #[arg(required = true)]
+        package_names: Vec<String>,
+        /// Build in a clean chroot with makechrootpkg (defaults to `chroot` from the config)
+        #[arg(long, value_name = "PATH", num_args = 0..=1)]
+        chroot: Option<Option<PathBuf>>,
+        /// Continue building the remaining packages after a failure
+        #[arg(long)]
+        keep_going: bool,
+    },
+    /// Rebuild given packages from scratch, even without upstream changes
+    #[command(name = "rebuild")]
+    Rebuild {
+        package_names: Vec<String>,
+        /// Rebuild all repositories
+        #[arg(long, conflicts_with = "package_names")]
+        all: bool,
+        /// Install the rebuilt packages
+        #[arg(long)]
+        install: bool,
+        /// Number of packages built at the same time (with more than one, missing dependencies
+        /// are not installed by makepkg)
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+        /// Continue rebuilding the remaining packages after a failure
+        #[arg(long)]
+        keep_going: bool,
+    },
+    /// Delete build artifacts from repositories and compress their git objects with `git gc`
+    ///
+    /// Artifacts are src/, pkg/, built packages, logs and downloaded sources, files tracked by
+    /// git are never deleted.
+    #[command(name = "clean")]
+    Clean {
+        package_names: Vec<String>,
+        /// Clean all repositories
+        #[arg(long, conflicts_with = "package_names")]
+        all: bool,
+        /// Only delete the build artifacts, without running `git gc`
+        #[arg(long)]
+        no_gc: bool,
+    },
+    /// Ignore updates of the given packages in fetch and upgrade until they are thawed
+    #[command(name = "freeze")]
+    Freeze {
+        #[arg(required = true)]
+        package_names: Vec<String>,
+    },
+    /// Flag an AUR package as out-of-date
+    #[command(name = "flag")]
+    Flag {
+        #[arg(value_name = completions::AUR_PACKAGE)]
+        package_name: String,
+        /// Why the package is out-of-date (e.g. the new upstream version)
+        comment: String,
+    },
+    /// Show the latest AUR comments of a package
+    #[command(name = "comments")]
+    Comments {
+        #[arg(value_name = completions::AUR_PACKAGE)]
+        package_name: String,
+        /// Number of comments to show
+        #[arg(short = 'n', long, default_value_t = 5)]
+        count: usize,
+    },
+    /// List the dependencies of an AUR package
+    #[command(name = "deps")]
+    Deps {
+        #[arg(value_name = completions::AUR_PACKAGE)]
+        package_name: String,
+        /// Show the dependency graph as an indented tree
+        #[arg(long)]
+        tree: bool,
+    },
+    /// Show the changes between a cloned repository and its upstream
+    #[command(name = "diff")]
+    Diff {
+        package_name: String,
+        /// Don't update the remote-tracking branch, so the repository is left untouched
+        #[arg(long)]
+        upstream_only: bool,
+    },
+    /// Compare the installed versions of foreign packages with the AUR, like `pacman -Qu`,
+    /// whether they are cloned or not
+    #[command(name = "check")]
+    Check,
+    /// Clone the repositories of all installed foreign packages found in the AUR, e.g. after
+    /// switching from another AUR helper
+    #[command(name = "adopt")]
+    Adopt {
+        /// Continue cloning the remaining packages after a failure
+        #[arg(long)]
+        keep_going: bool,
+    },
+    /// List cloned repositories whose packages are not installed (anymore)
+    #[command(name = "orphans")]
+    Orphans {
+        /// Remove the repositories of packages that are not installed at all
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Delete the repositories of packages that are not installed anymore, after confirming
+    /// (same as `orphans --remove`)
+    #[command(name = "prune")]
+    Prune,
+    /// List AUR packages that depend on the given package
+    #[command(name = "provides")]
+    Provides { package_name: String },
+    /// Stop ignoring updates of the given frozen packages
+    #[command(name = "thaw")]
+    Thaw {
+        #[arg(required = true)]
+        package_names: Vec<String>,
+    },
+    /// Print the path of a cloned repository
+    #[command(name = "which")]
+    Which {
+        package_name: String,
+        /// Also check that the directory is a valid git repository
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Open the PKGBUILD (or another file) of a cloned package in $VISUAL or $EDITOR
+    ///
+    /// Changed files are protected: pull refuses to overwrite them until they are committed or
+    /// discarded.
+    #[command(name = "edit")]
+    Edit {
+        package_name: String,
+        /// File to edit, relative to the repository
+        #[arg(default_value = "PKGBUILD")]
+        file: String,
+    },
+    /// Stop tracking cloned packages and delete their repositories
+    ///
+    /// Installed packages are offered to be uninstalled with `pacman -R` afterwards.
+    #[command(name = "remove")]
+    Remove {
+        #[arg(required = true)]
+        package_names: Vec<String>,
+        /// Keep the repositories and only add them to .taurignore, so they are left out of
+        /// fetch, upgrade and --all
+        #[arg(long)]
+        keep_files: bool,
+        /// Uninstall the packages without offering it first (still asks pacman's question
+        /// unless --no-confirm is given)
+        #[arg(long)]
+        uninstall: bool,
+    },
+    /// Print the PKGBUILD or another file of a cloned package, as checked out
+    #[command(name = "show")]
+    Show {
+        package_name: String,
+        /// File to print, relative to the repository
+        #[arg(default_value = "PKGBUILD")]
+        file: String,
+        /// Show the file in a pager (bat with syntax highlighting if it is installed, $PAGER
+        /// or less otherwise)
+        #[arg(long)]
+        pager: bool,
+    },
+    /// Show or change settings of the config file
+    #[command(name = "config", subcommand)]
+    Config(ConfigAction),
+    /// Check the config, the repos directory, the programs taur runs and the connection to the
+    /// AUR, suggesting fixes for any problems
+    #[command(name = "doctor")]
+    Doctor,
+    /// Store the session of a logged-in AUR account in the system keyring
+    #[command(name = "login")]
+    Login,
+    /// Write systemd user units running `taur fetch` periodically
+    Timer {
+        /// Time between two fetches, as a systemd time span like `30min` or `2h`
+        #[arg(long, default_value = "1h")]
+        interval: String,
+        /// Let the fetch send a desktop notification if packages have upstream changes
+        #[arg(long)]
+        notify: bool,
+        /// Remove the units instead
+        #[arg(long, conflicts_with = "notify")]
+        uninstall: bool,
+    },
+    /// Vote for an AUR package
+    #[command(name = "vote")]
+    Vote {
+        #[arg(value_name = completions::AUR_PACKAGE)]
+        package_name: String,
+    },
+    /// Check the checksums of a package's sources, then download and verify them without
+    /// building
+    #[command(name = "verify")]
+    Verify { package_name: String },
+    /// Fetch all repositories, then pull, rebuild and reinstall the ones with upstream changes
+    #[command(name = "upgrade")]
+    Upgrade {
+        /// Only upgrade these of the packages with upstream changes (defaults to all of them)
+        package_names: Vec<String>,
+        /// Build packages again even if their current version was already built
+        #[arg(long)]
+        force: bool,
+        /// Build in a clean chroot with makechrootpkg (defaults to `chroot` from the config)
+        #[arg(long, value_name = "PATH", num_args = 0..=1)]
+        chroot: Option<Option<PathBuf>>,
+        /// Continue upgrading the remaining packages after a failure
+        #[arg(long)]
+        keep_going: bool,
+        /// Pull without showing the changes to PKGBUILD and install files and asking first
+        #[arg(long)]
+        skip_review: bool,
+    },
 }
 
-#[derive(Eq)]
-struct UpdateInfo {
-    name: String,
-    commits: Vec<String>,
-}
+impl Command {
+    /// Describes what the command would do, for `--dry-run`
+    ///
+    /// Returns None for read-only commands and for commands that do their own dry run (like
+    /// `upgrade`, which lists the packages that would be upgraded), so they still run.
+    fn dry_run(&self) -> Option<String> {
+        let description = match self {
+            Command::Build { package_names, .. } => {
+                format!("build {}", package_names.join(", "))
+            }
+            Command::Clone {
+                package_names,
+                force,
+                recursive,
+                branch,
+                at_version,
+                ..
+            } => {
+                let mut description = format!("clone {}", package_names.join(", "));
+                if let Some(branch) = branch {
+                    description.push_str(&format!(" (branch '{}')", branch));
+                }
+                if let Some(version) = at_version {
+                    description.push_str(&format!(" at version {}", version));
+                }
+                if *recursive {
+                    description.push_str(" and their AUR dependencies");
+                }
+                if *force {
+                    description.push_str(", replacing existing repositories");
+                }
+                description
+            }
+            Command::Fetch { .. } => String::from("fetch and check all repositories"),
+            Command::Flag { package_name, .. } => {
+                format!("flag '{}' as out-of-date", package_name)
+            }
+            Command::Freeze { package_names } => format!("freeze {}", package_names.join(", ")),
+            Command::Get {
+                expression,
+                install,
+            } => format!(
+                "search AUR for '{}' and clone{} the picked package",
+                expression,
+                if *install { ", build and install" } else { "" }
+            ),
+            Command::Install { package_names, .. } => {
+                format!(
+                    "clone or pull, build and install {}",
+                    package_names.join(", ")
+                )
+            }
+            Command::List { fetch: true, .. } => String::from("fetch and list all repositories"),
+            Command::Login => String::from("store an AUR session in the keyring"),
+            Command::Pull {
+                package_names, all, ..
+            } => match all {
+                true => String::from("pull all repositories"),
+                false => format!("pull {}", package_names.join(", ")),
+            },
+            Command::Rebuild {
+                package_names,
+                all,
+                install,
+                ..
+            } => format!(
+                "rebuild {}{}",
+                match all {
+                    true => String::from("all packages"),
+                    false => package_names.join(", "),
+                },
+                if *install { " and install them" } else { "" }
+            ),
+            Command::Clean {
+                package_names,
+                all,
+                no_gc,
+            } => format!(
+                "delete the build artifacts of {}{}",
+                match all {
+                    true => String::from("all repositories"),
+                    false => package_names.join(", "),
+                },
+                if *no_gc { "" } else { " and run git gc" }
+            ),
+            Command::SyncDb { .. } => String::from("download the AUR package database"),
+            Command::Search {
+                expression,
+                select: true,
+                ..
+            } => format!("search for '{}' and clone the picked packages", expression),
+            Command::Thaw { package_names } => format!("thaw {}", package_names.join(", ")),
+            Command::Timer {
+                uninstall: true, ..
+            } => format!("stop and remove {} and {}", timer::SERVICE, timer::TIMER),
+            Command::Timer { interval, .. } => format!(
+                "write {} and {} running fetch every {}",
+                timer::SERVICE,
+                timer::TIMER,
+                interval
+            ),
+            Command::Edit { package_name, file } => {
+                format!("edit {} of '{}'", file, package_name)
+            }
+            Command::Remove {
+                package_names,
+                keep_files,
+                uninstall,
+            } => format!(
+                "{} {}{}",
+                if *keep_files { "untrack" } else { "delete" },
+                package_names.join(", "),
+                if *uninstall {
+                    " and uninstall them"
+                } else {
+                    ""
+                }
+            ),
+            Command::Tui => String::from("browse the repositories interactively"),
+            Command::Verify { package_name } => {
+                format!("download and verify the sources of '{}'", package_name)
+            }
+            Command::Vote { package_name } => format!("vote for '{}'", package_name),
+            Command::Adopt { .. }
+            | Command::Check
+            | Command::Comments { .. }
+            | Command::Complete { .. }
+            | Command::Completions { .. }
+            | Command::Config(_)
+            | Command::Deps { .. }
+            | Command::Diff { .. }
+            | Command::Doctor
+            | Command::Info { .. }
+            | Command::List { fetch: false, .. }
+            | Command::Order { .. }
+            | Command::Orphans { .. }
+            | Command::Prune
+            | Command::Provides { .. }
+            | Command::Search { select: false, .. }
+            | Command::Show { .. }
+            | Command::Upgrade { .. }
+            | Command::Which { .. } => return None,
+        };
 
-impl Display for UpdateInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        writeln!(
-            f,
-            "{}{}:: {}{}{}",
-            style::Bold,
-            color::Fg(color::Blue),
-            color::Fg(color::Reset),
-            self.name,
-            style::Reset
-        )?;
-        writeln!(f)?;
+        Some(description)
+    }
 
-        for commit in &self.commits {
-            writeln!(
-                f,
-                "{}* {}{}{}",
-                color::Fg(color::Magenta),
-                color::Fg(color::Cyan),
-                commit,
-                style::Reset
-            )?;
+    /// Returns true if the command changes repositories or taur's state, so it must not run
+    /// concurrently with another taur
+    fn mutates(&self) -> bool {
+        match self {
+            Command::Adopt { .. }
+            | Command::Build { .. }
+            | Command::Clean { .. }
+            | Command::Clone { .. }
+            | Command::Edit { .. }
+            | Command::Fetch { .. }
+            | Command::Freeze { .. }
+            | Command::Get { .. }
+            | Command::Install { .. }
+            | Command::Prune
+            | Command::Pull { .. }
+            | Command::Rebuild { .. }
+            | Command::Remove { .. }
+            | Command::Thaw { .. }
+            | Command::Tui
+            | Command::Upgrade { .. }
+            | Command::Verify { .. } => true,
+            Command::Diff { upstream_only, .. } => !upstream_only,
+            Command::List { fetch, .. } => *fetch,
+            Command::Orphans { remove } => *remove,
+            Command::Search { select, .. } => *select,
+            Command::Check
+            | Command::Comments { .. }
+            | Command::Complete { .. }
+            | Command::Completions { .. }
+            | Command::Config(_)
+            | Command::Deps { .. }
+            | Command::Doctor
+            | Command::Flag { .. }
+            | Command::Info { .. }
+            | Command::Login
+            | Command::Order { .. }
+            | Command::Provides { .. }
+            | Command::Show { .. }
+            | Command::SyncDb { .. }
+            | Command::Timer { .. }
+            | Command::Vote { .. }
+            | Command::Which { .. } => false,
         }
-
-        Ok(())
     }
 }
 
-impl Ord for UpdateInfo {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.name.cmp(&other.name)
-    }
+#[derive(Eq, Serialize)]
+struct UpdateInfo {
+    name: String,
+    commits: Vec<String>,
+    /// Signature status of each commit, if signatures were verified
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    signatures: Vec<SignatureStatus>,
+    /// Number of incoming commits left out because of `--max-commits`
+    omitted: usize,
+    /// Number of local commits that are not upstream
+    ahead: usize,
+    /// Risky changes in the incoming build files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    audit: Vec<Finding>,
 }
 
-impl PartialEq for UpdateInfo {
-    fn eq(&self, other: &UpdateInfo) -> bool {
-        self.name == other.name
+impl UpdateInfo {
+    /// Returns the number of incoming commits, including the omitted ones
+    fn behind(&self) -> usize {
+        self.commits.len() + self.omitted
     }
-}
 
-impl PartialOrd for UpdateInfo {
-    fn partial_cmp(&self, other: &UpdateInfo) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    /// Returns true if the local branch is ahead of upstream, but not behind
+    fn is_ahead_only(&self) -> bool {
+        self.ahead > 0 && self.commits.is_empty() && self.omitted == 0
     }
-}
-
-#[tokio::main]
-async fn main() {
-    let args = Args::parse();
 
-    let proj_dirs =
-        ProjectDirs::from("", "", "taur").expect("Unable to retrieve application directories");
+    fn print(&self, out: &Formatter) {
+        let ahead = if self.ahead > 0 {
+            format!(" {}↑{}", out.fg(out.theme.warning), self.ahead)
+        } else {
+            String::new()
+        };
+        println!(
+            "{}{}:: {}{}{}{}",
+            out.paint(style::Bold),
+            out.fg(out.theme.header),
+            out.paint(color::Fg(color::Reset)),
+            self.name,
+            ahead,
+            out.paint(style::Reset)
+        );
+        if self.ahead > 0 {
+            println!(
+                "{}({} local {} not upstream, pulling would not fast-forward){}",
+                out.fg(out.theme.warning),
+                self.ahead,
+                if self.ahead == 1 { "commit" } else { "commits" },
+                out.paint(style::Reset)
+            );
+        }
+        println!();
 
-    match &args.command {
-        Some(cmd) => match cmd {
-            Command::Clone { package_name } => {
-                if let Err(e) = clone(proj_dirs, args.repos, package_name).await {
-                    eprintln!("Error while cloning: {}", e);
-                }
-            }
-            Command::Fetch => {
-                if let Err(e) = fetch(proj_dirs, args.repos).await {
-                    eprintln!("Error while fetching: {}", e);
-                }
-            }
-            Command::Pull { package_names } => {
-                if let Err(e) = pull(proj_dirs, args.repos, package_names).await {
-                    eprintln!("Error while pulling: {}", e);
-                }
-            }
-            Command::Search { expression } => {
-                if let Err(e) = search(expression).await {
-                    eprintln!("Error while searching: {}", e);
-                }
-            }
-        },
-        None => {
-            if let Err(e) = fetch(proj_dirs, args.repos).await {
-                eprintln!("Error while fetching: {}", e);
+        for (i, commit) in self.commits.iter().enumerate() {
+            match self.signatures.get(i) {
+                Some(status) => print_signed_commit(out, commit, *status),
+                None => print_commit(out, commit),
             }
         }
+
+        let unverified = self
+            .signatures
+            .iter()
+            .filter(|s| **s != SignatureStatus::Verified)
+            .count();
+        if unverified > 0 {
+            println!(
+                "{}{}Warning: {} of {} incoming commits are not signed by a known key{}",
+                out.paint(style::Bold),
+                out.fg(out.theme.warning),
+                unverified,
+                self.signatures.len(),
+                out.paint(style::Reset)
+            );
+        }
+
+        if self.omitted > 0 {
+            println!(
+                "{}(+{} more){}",
+                out.fg(out.theme.commit),
+                self.omitted,
+                out.paint(style::Reset)
+            );
+        }
+
+        if !self.audit.is_empty() {
+            println!();
+            print!("{}", format_findings(out, &self.audit));
+        }
+
+        println!();
     }
 }
 
-async fn clone(
-    proj_dirs: ProjectDirs,
-    repos: Option<PathBuf>,
-    package_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let raur = raur::Handle::new();
-    let pkgs = raur.info(&[package_name]).await?;
-
-    if pkgs.is_empty() {
-        return Err(Box::new(Error::new(
-            ErrorKind::NotFound,
-            format!("Package '{}' not found", package_name),
-        )));
+/// Formats the findings of the audit, one line each
+fn format_findings(out: &Formatter, findings: &[Finding]) -> String {
+    let mut text = String::new();
+    for finding in findings {
+        let line = if finding.line.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", finding.line)
+        };
+        text.push_str(&format!(
+            "{}{}! {}{} {}{}{}{}\n",
+            out.paint(style::Bold),
+            out.fg(out.theme.warning),
+            finding.file,
+            out.paint(style::Reset),
+            out.fg(out.theme.warning),
+            finding.reason,
+            line,
+            out.paint(style::Reset)
+        ));
     }
+    text
+}
 
-    let repo_path = get_repo_path(proj_dirs, repos);
-    if !repo_path.exists() {
-        std::fs::create_dir_all(repo_path.as_ref())?;
+/// A repository whose checked out branch does not track a remote branch
+#[derive(Debug)]
+struct NoUpstream {
+    name: String,
+    branch: String,
+}
+
+impl Display for NoUpstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}: no upstream configured for branch '{}'; run \
+             'git branch --set-upstream-to=origin/{} {}' inside the repository",
+            self.name, self.branch, self.branch, self.branch
+        )
     }
+}
 
-    let repo_path = repo_path.join(package_name);
+impl std::error::Error for NoUpstream {}
 
-    let url = format!("https://aur.archlinux.org/{}.git", package_name);
+/// Options of the fetch command
+#[derive(Clone, Default)]
+struct FetchOptions {
+    quiet: bool,
+    exit_code: bool,
+    /// Report failures as warnings and never fail the command
+    ignore_errors: bool,
+    status_format: Option<StatusFormat>,
+    summary: bool,
+    timings: bool,
+    /// Only show commits whose message matches
+    grep: Option<Regex>,
+    /// Send a desktop notification about upstream changes
+    notify: bool,
+    check: CheckOptions,
+}
 
-    match Repository::clone(&url, &repo_path) {
-        Ok(_) => println!("Cloned repo '{}' to '{:?}'", package_name, repo_path),
-        Err(e) => {
-            return Err(Box::new(Error::new(
-                ErrorKind::Other,
-                format!("Error while cloning repo '{}': {}", package_name, e),
-            )))
-        }
-    };
+/// Options for checking repositories for updates
+#[derive(Clone, Copy, Default)]
+struct CheckOptions {
+    max_commits: Option<usize>,
+    /// Only fetch repositories whose last fetch is at least this many seconds ago
+    stale: Option<u64>,
+    verify_signatures: bool,
+    /// Fetch even if the remote's branch tip did not change
+    deep: bool,
+    /// Compare with the state of the last fetch instead of fetching
+    offline: bool,
+    /// Also check repositories in subdirectories
+    recursive_scan: bool,
+}
 
-    Ok(())
+/// When to fetch a repository before checking it for updates
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FetchMode {
+    /// Use the state of the last fetch
+    Never,
+    /// Fetch if the remote's branch tip differs from the last fetched one
+    IfMoved,
+    Always,
 }
 
-async fn fetch(
-    proj_dirs: ProjectDirs,
-    repos: Option<PathBuf>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let repo_path = get_repo_path(proj_dirs, repos);
-    if !repo_path.exists() {
-        std::fs::create_dir_all(repo_path.as_ref())?;
-    }
+/// Number of repositories cloned or pulled at the same time without `--jobs` or the setting
+const DEFAULT_JOBS: usize = 4;
 
-    let dirs = get_dir_list(&repo_path)?;
+/// Options of the pull command
+#[derive(Clone, Copy, Default)]
+struct PullOptions {
+    all: bool,
+    recursive_scan: bool,
+    require_signatures: bool,
+    jobs: usize,
+    keep_going: bool,
+    /// Show the changes to the build files and ask before pulling each package
+    review: bool,
+    no_confirm: bool,
+}
 
-    let mut update_infos: Vec<UpdateInfo> = Vec::new();
+/// How `pull_package` shows the changes to the build files before updating the working tree
+#[derive(Clone, Copy)]
+struct Review {
+    out: Formatter,
+    /// Only show the changes, without asking
+    no_confirm: bool,
+}
 
-    let (tx, rx) = mpsc::channel();
-    let mut join_handles = vec![];
+/// Result of pulling a single package, printed once all pulls are done
+#[derive(Debug, Serialize)]
+struct PullResult {
+    name: String,
+    #[serde(flatten)]
+    status: PullStatus,
+}
 
-    for dir in dirs {
-        let tx = mpsc::Sender::clone(&tx);
-        let path_base = repo_path.clone();
-        join_handles.push(task::spawn_blocking(move || {
-            let full_path = path_base.join(dir);
-            match check_repo_updates(full_path) {
-                Ok(update_info) => {
-                    if let Some(update_info) = update_info {
-                        if let Err(e) = tx.send(update_info) {
-                            eprintln!("Error while sending update info for printing: {}", e);
-                        }
-                    }
-                }
-                Err(e) => eprintln!("Error while checking for updates for repo {:?}", e),
-            }
-        }));
-    }
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+enum PullStatus {
+    /// Fast-forwarded to `head`, bringing in the given commits
+    Pulled {
+        head: String,
+        commits: Vec<String>,
+        /// Risky changes in the pulled build files
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        audit: Vec<Finding>,
+    },
+    UpToDate,
+    /// Not pulled, as the changes were rejected in the review
+    Declined,
+    Failed {
+        error: String,
+    },
+}
 
-    // Drop tx to get rid of the original unused sender
-    drop(tx);
+/// Orders of `list`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum SortBy {
+    /// Alphabetically
+    Name,
+    /// Most recent HEAD commit first
+    Updated,
+    /// Most commits behind upstream first
+    Behind,
+}
 
-    futures::future::join_all(join_handles).await;
+/// Fields of the AUR RPC `search` can match against, named like its `by` parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum SearchField {
+    /// Package name
+    Name,
+    /// Package name and description
+    NameDesc,
+    /// Maintainer
+    Maintainer,
+    /// Co-maintainers
+    #[value(name = "comaintainers")]
+    CoMaintainers,
+    /// Submitter
+    Submitter,
+    /// Dependencies
+    Depends,
+    /// Make dependencies
+    #[value(name = "makedepends")]
+    MakeDepends,
+    /// Optional dependencies
+    #[value(name = "optdepends")]
+    OptDepends,
+    /// Check dependencies
+    #[value(name = "checkdepends")]
+    CheckDepends,
+    /// Provided packages
+    Provides,
+    /// Conflicting packages
+    Conflicts,
+    /// Replaced packages
+    Replaces,
+    /// Groups
+    Groups,
+    /// Keywords
+    Keywords,
+}
 
-    for received in rx {
-        update_infos.push(received);
+impl From<SearchField> for SearchBy {
+    fn from(field: SearchField) -> SearchBy {
+        match field {
+            SearchField::Name => SearchBy::Name,
+            SearchField::NameDesc => SearchBy::NameDesc,
+            SearchField::Maintainer => SearchBy::Maintainer,
+            SearchField::CoMaintainers => SearchBy::CoMaintainers,
+            SearchField::Submitter => SearchBy::Submitter,
+            SearchField::Depends => SearchBy::Depends,
+            SearchField::MakeDepends => SearchBy::MakeDepends,
+            SearchField::OptDepends => SearchBy::OptDepends,
+            SearchField::CheckDepends => SearchBy::CheckDepends,
+            SearchField::Provides => SearchBy::Provides,
+            SearchField::Conflicts => SearchBy::Conflicts,
+            SearchField::Replaces => SearchBy::Replaces,
+            SearchField::Groups => SearchBy::Groups,
+            SearchField::Keywords => SearchBy::Keywords,
+        }
     }
+}
 
-    print_update_info(update_infos);
+/// State of a cloned repository as shown by `list`
+#[derive(Serialize)]
+struct RepoEntry {
+    name: String,
+    /// Version of the checked out `.SRCINFO`
+    version: Option<String>,
+    /// Abbreviated id of the checked out commit
+    head: String,
+    /// Time of the checked out commit in seconds since the Unix epoch
+    updated: i64,
+    /// Number of upstream commits not pulled yet, as of the last fetch
+    behind: usize,
+    /// Number of local commits not upstream
+    ahead: usize,
+    /// Whether tracked files were modified
+    dirty: bool,
+    /// Time the checked out branch was last moved (by the clone or a pull) in seconds since the
+    /// Unix epoch
+    last_pulled: Option<i64>,
+    /// Seconds since the last successful fetch
+    last_checked: Option<u64>,
+    /// Whether it is a VCS package, whose upstream is only tracked when building
+    vcs: bool,
+    /// Full id of the checked out commit, for `--porcelain`
+    #[serde(skip)]
+    head_id: String,
+}
 
-    Ok(())
+/// Why a cloned repository is considered an orphan
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum OrphanKind {
+    /// None of its packages is installed
+    NotInstalled,
+    /// Its packages are installed, but from the official repositories
+    OtherSource,
 }
 
-async fn search(expression: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let raur = raur::Handle::new();
+#[derive(Serialize)]
+struct Orphan {
+    name: String,
+    kind: OrphanKind,
+}
 
-    let mut pkgs = raur.search(expression).await?;
-    pkgs.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+/// Options of the clone command
+#[derive(Clone, Default)]
+struct CloneOptions {
+    force: bool,
+    recursive: bool,
+    branch: Option<String>,
+    at_version: Option<String>,
+    jobs: usize,
+    keep_going: bool,
+    no_confirm: bool,
+    scheme: CloneScheme,
+}
 
-    if pkgs.is_empty() {
-        println!("No packages found");
-        return Ok(());
-    }
+/// Options of the rebuild command
+struct RebuildOptions {
+    all: bool,
+    install: bool,
+    jobs: usize,
+    keep_going: bool,
+    build: BuildOptions,
+}
 
-    let longest_len = pkgs
-        .iter()
-        .max_by_key(|p| p.name.len())
-        .map(|p| p.name.len())
-        .unwrap_or_default();
+/// Options of the list command
+struct ListOptions {
+    sort_by: SortBy,
+    porcelain: bool,
+    /// Fetch before listing, otherwise only local refs are read
+    fetch: bool,
+}
 
-    println!(
-        "{}Pop  - Name{}Description{}",
-        style::Bold,
-        " ".repeat(std::cmp::max(longest_len - 3, 0)),
-        style::Reset
-    );
+/// Options of the upgrade command
+struct UpgradeOptions {
+    /// Packages to upgrade, all packages with upstream changes if empty
+    selected: Vec<String>,
+    dry_run: bool,
+    keep_going: bool,
+    /// Show the changes to the build files of each package and ask before pulling it
+    review: bool,
+    /// Also look for repositories in subdirectories
+    recursive_scan: bool,
+    build: BuildOptions,
+}
 
-    for pkg in pkgs {
-        println!(
-            "{:.2} - {}{}{}{}{}",
-            pkg.popularity,
-            color::Fg(color::Magenta),
-            pkg.name,
-            style::Reset,
-            " ".repeat(std::cmp::max(longest_len - pkg.name.len() + 1, 0)),
-            pkg.description.unwrap_or_default()
-        );
-    }
+/// Options of the install command
+struct InstallOptions {
+    build: BuildOptions,
+    scheme: CloneScheme,
+    keep_going: bool,
+    /// Show the changes to the build files of cloned packages and ask before pulling them
+    review: bool,
+}
 
-    Ok(())
+/// Decides whether a batch command starts more work after a failure
+///
+/// By default, batch commands stop starting new work after the first failure (work that is
+/// already running is still finished and reported). With `--keep-going`, they go through all
+/// packages and report every failure at the end.
+struct FailurePolicy {
+    keep_going: bool,
+    failed: AtomicBool,
 }
 
-async fn pull(
-    proj_dirs: ProjectDirs,
-    repos: Option<PathBuf>,
-    package_names: &[String],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let repo_path = get_repo_path(proj_dirs, repos);
-    if !repo_path.exists() {
-        std::fs::create_dir_all(repo_path.as_ref())?;
+impl FailurePolicy {
+    fn new(keep_going: bool) -> Self {
+        FailurePolicy {
+            keep_going,
+            failed: AtomicBool::new(false),
+        }
     }
 
-    let mut join_handles = vec![];
+    fn record_failure(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
 
-    for package_name in package_names {
-        let package_name = package_name.clone();
-        let path_base = repo_path.clone();
-        join_handles.push(task::spawn_blocking(move || {
-            if let Err(e) = pull_package(&path_base, &package_name) {
-                eprintln!("Error while pulling package: {:?}", e);
-            }
-        }));
+    /// Returns true if more work may be started
+    fn proceed(&self) -> bool {
+        self.keep_going || !self.failed.load(Ordering::Relaxed)
     }
 
-    futures::future::join_all(join_handles).await;
+    /// Builds the error ending a batch command with failures, mentioning the skipped work
+    fn error(&self, message: String, skipped: usize) -> Error {
+        if skipped == 0 {
+            return Error::other(message);
+        }
 
-    Ok(())
+        Error::other(format!(
+            "{}, stopped before the remaining {} (use --keep-going to continue after failures)",
+            message, skipped
+        ))
+    }
 }
 
-fn pull_package(repo_path: &Path, package_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let full_path = repo_path.join(package_name);
+/// Outcome of checking a single repository for updates
+enum CheckOutcome {
+    Checked(Option<UpdateInfo>),
+    Skipped(String),
+    Failed(FailureKind, String),
+}
 
-    let repo = Repository::open(&full_path)?;
+/// Causes of failed checks, to tell recurring problems apart when many repositories fail
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum FailureKind {
+    Network,
+    Auth,
+    Damaged,
+    NotFound,
+    Other,
+}
 
-    let update_info = check_repo_updates(full_path)?;
+impl FailureKind {
+    const ALL: [FailureKind; 5] = [
+        FailureKind::Network,
+        FailureKind::Auth,
+        FailureKind::Damaged,
+        FailureKind::NotFound,
+        FailureKind::Other,
+    ];
 
-    match update_info {
-        Some(update_info) => {
-            println!("{}Pulling {}...{}", style::Bold, package_name, style::Reset);
-            println!();
-            for commit in update_info.commits {
-                println!(
-                    "{}* {}{}{}",
-                    color::Fg(color::Magenta),
-                    color::Fg(color::Cyan),
-                    commit,
-                    style::Reset
-                );
-            }
+    /// Classifies the error of checking a repository
+    fn of(e: &(dyn std::error::Error + 'static)) -> FailureKind {
+        let auth = e
+            .downcast_ref::<Error>()
+            .and_then(|e| e.get_ref())
+            .is_some_and(|inner| inner.is::<remote::AuthError>());
+        if auth {
+            return FailureKind::Auth;
         }
-        None => {
-            println!("No new commits to pull");
-            return Ok(());
+        if corruption(e).is_some() {
+            return FailureKind::Damaged;
+        }
+
+        // Only errors of remote operations are wrapped (by `remote::explain`), so system errors
+        // among them come from connecting, like a refused connection
+        let remote = e.is::<Error>();
+        match git_error(e) {
+            Some(e) if e.code() == ErrorCode::Auth => FailureKind::Auth,
+            Some(e) if e.code() == ErrorCode::NotFound => FailureKind::NotFound,
+            Some(e) => match e.class() {
+                ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssl => FailureKind::Network,
+                ErrorClass::Os if remote => FailureKind::Network,
+                _ => FailureKind::Other,
+            },
+            None => FailureKind::Other,
         }
     }
+}
 
-    let fetch_head = repo.find_reference("FETCH_HEAD")?;
-    let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let label = match self {
+            FailureKind::Network => "network",
+            FailureKind::Auth => "authentication",
+            FailureKind::Damaged => "damaged",
+            FailureKind::NotFound => "not found",
+            FailureKind::Other => "other",
+        };
+        f.write_str(label)
+    }
+}
 
-    let mut refs_heads_master = repo.find_reference("refs/heads/master")?;
+/// Outcome of checking all repositories for updates
+#[derive(Default)]
+struct CheckResults {
+    update_infos: Vec<UpdateInfo>,
+    /// Warnings about repositories that were left out on purpose
+    skipped: Vec<String>,
+    /// Repositories that could not be checked together with the cause and reason
+    failures: Vec<(String, FailureKind, String)>,
+    /// Repositories that were fetched successfully
+    fetched: Vec<String>,
+    /// Repositories that were not fetched as they were fetched recently, with the age in seconds
+    recent: Vec<(String, u64)>,
+    /// Time it took to fetch and check each repository
+    timings: Vec<(String, Duration)>,
+    /// Repositories of VCS packages, which can be outdated without new commits
+    vcs: Vec<String>,
+}
 
-    let name = match refs_heads_master.name() {
-        Some(name) => name.to_string(),
-        None => String::from_utf8_lossy(refs_heads_master.name_bytes()).to_string(),
-    };
+#[derive(Serialize)]
+struct SearchResult {
+    name: String,
+    version: String,
+    description: Option<String>,
+    popularity: f64,
+    votes: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cloned: Option<bool>,
+}
 
-    let msg = format!(
-        "Fast-Forward: Setting {} to id: {}",
-        name,
-        fetch_commit.id()
-    );
-    refs_heads_master.set_target(fetch_commit.id(), &msg)?;
+/// Options of the search command
+#[derive(Clone, Copy)]
+struct SearchOptions {
+    marking: CloneMarking,
+    exact: bool,
+    limit: Option<usize>,
+    page: usize,
+    /// Columns descriptions are cut to fit into
+    width: Option<usize>,
+    /// Search the downloaded package database
+    offline: bool,
+    /// Field the expression is matched against
+    by: SearchField,
+    /// Ask which results to clone, with this scheme
+    select: Option<CloneScheme>,
+}
 
-    repo.set_head(&name)?;
+/// How search results are matched against the locally cloned repositories
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CloneMarking {
+    None,
+    Mark,
+    Only,
+}
 
-    let checkout = &mut git2::build::CheckoutBuilder::default();
-    checkout.force();
-    repo.checkout_head(Some(checkout))?;
+#[derive(Serialize)]
+struct Dependency<'a> {
+    name: &'a str,
+    source: Source,
+    cloned: bool,
+    depends: &'a [String],
+}
 
-    Ok(())
+#[derive(Serialize)]
+struct Dependent<'a> {
+    name: String,
+    kinds: Vec<&'a str>,
+    cloned: bool,
 }
 
-fn get_repo_path(proj_dirs: ProjectDirs, repos: Option<PathBuf>) -> Box<PathBuf> {
-    match repos {
-        Some(s) => Box::new(s),
-        None => Box::new(proj_dirs.data_dir().join("repos")),
+impl Ord for UpdateInfo {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.name.cmp(&other.name)
     }
 }
 
-fn print_update_info(mut update_infos: Vec<UpdateInfo>) {
-    if !update_infos.is_empty() {
-        println!(
-            "{}The following packages have upstream changes:{}",
-            style::Bold,
-            style::Reset
-        );
-        println!();
-
-        update_infos.sort_unstable();
-
-        for info in update_infos {
-            println!("{}", info);
-        }
-    } else {
-        println!("There are currently no packages with upstream changes");
+impl PartialEq for UpdateInfo {
+    fn eq(&self, other: &UpdateInfo) -> bool {
+        self.name == other.name
     }
 }
 
-fn check_repo_updates(path: PathBuf) -> Result<Option<UpdateInfo>, Box<dyn std::error::Error>> {
-    let dir_name = path.file_name().ok_or("File name was None?!")?;
-    let dir_name = String::from(dir_name.to_string_lossy());
-
-    let repo = Repository::open(path)?;
-    let mut remote = repo.find_remote("origin")?;
-    remote.fetch(&["master"], None, None)?;
+impl PartialOrd for UpdateInfo {
+    fn partial_cmp(&self, other: &UpdateInfo) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-    let local_rev = repo.revparse_single("HEAD")?;
-    let remote_rev = repo.revparse_single("@{u}")?;
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = Args::parse();
+    if let Some(path) = args.legacy_repos.take() {
+        eprintln!(
+            "Warning: Giving the repos directory as first argument is deprecated, use --repos {}",
+            path.display()
+        );
+        args.repos.get_or_insert(path);
+    }
+    if args.json {
+        args.output = Some(OutputFormat::Json);
+    }
 
-    if local_rev.id() != remote_rev.id() {
-        let mut revwalk = repo.revwalk()?;
+    let proj_dirs = match &args.profile {
+        Some(profile) => ProjectDirs::from_path(["taur", "profiles", profile].iter().collect()),
+        None => ProjectDirs::from("", "", "taur"),
+    }
+    .expect("Unable to retrieve application directories");
 
-        revwalk.push(remote_rev.id())?;
-        revwalk.hide(local_rev.id())?;
+    // Don't require a valid config for fixing it
+    if let Some(Command::Config(action)) = &args.command {
+        if let Err(e) = configure(&proj_dirs, action, args.dry_run) {
+            eprintln!("Error while configuring: {}", e);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
 
-        // println!("Local: {}", local_rev.id());
-        // println!("Remote: {}", remote_rev.id());
+    // Diagnosing a broken config must not fail on it
+    if let Some(Command::Doctor) = &args.command {
+        let color = args.color.unwrap_or_default();
+        let output = args.output.unwrap_or_default();
+        let out = Formatter::new(output, color.enabled(), Theme::default());
+        if let Err(e) = doctor(&proj_dirs, &args, &out).await {
+            eprintln!("Error while diagnosing: {}", e);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
 
-        let mut commits: Vec<String> = Vec::new();
+    let config = match Config::load(&proj_dirs) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error while reading config: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-        for oid in revwalk {
-            let oid = oid?;
-            let commit = repo.find_commit(oid)?;
-            if let Some(c) = commit.message() {
-                commits.push(String::from(c));
-            }
+    // An invalid default command is reported even when another command is given
+    let command = match (default_command_of(&config), args.command.take()) {
+        (Err(e), _) => {
+            eprintln!("Error while reading config: {}", e);
+            return ExitCode::FAILURE;
         }
+        (Ok(_), Some(command)) => command,
+        (Ok((command, defaults)), None) => {
+            merge_defaults(&mut args, defaults);
+            command
+        }
+    };
+    let command = &command;
+    let (no_confirm, dry_run) = (args.no_confirm, args.dry_run);
 
-        return Ok(Some(UpdateInfo {
-            name: dir_name,
-            commits,
-        }));
+    let theme = match Theme::from_config(&config.colors) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("Error while reading colors from config: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let color = args.color.or(config.color).unwrap_or_default();
+    let out = Formatter::new(args.output.unwrap_or_default(), color.enabled(), theme);
+    args.repos = match repos_of(&args, &proj_dirs, &config) {
+        Ok(repos) => repos,
+        Err(e) => {
+            eprintln!("Error while reading config: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let auth = RemoteAuth::new(config.ssh_key.clone());
+    // makepkg runs inside the repositories, so a relative directory must not be passed on as is
+    let output_dir = args
+        .output_dir
+        .clone()
+        .or_else(|| config.output_dir.clone())
+        .map(|dir| std::path::absolute(&dir).unwrap_or(dir));
+    let tools = tools_of(&args, &config);
+
+    // Read-only commands ignore --dry-run
+    let description = command.dry_run();
+    if dry_run && (description.is_some() || command.mutates()) {
+        eprintln!("(dry run)");
+        if let Some(description) = description {
+            println!("Would {}", description);
+            return ExitCode::SUCCESS;
+        }
+    }
+
+    // Held until taur exits, a dry run changes nothing
+    let _lock = if command.mutates() && !dry_run {
+        match Lock::acquire(&proj_dirs) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("Error while locking: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        None
+    };
+
+    match command {
+        // Handled before the config is loaded
+        Command::Config(_) => {}
+        // Only reached as the default command, so the config is known to be valid
+        Command::Doctor => {
+            if let Err(e) = doctor(&proj_dirs, &args, &out).await {
+                eprintln!("Error while diagnosing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Clone {
+            package_names,
+            force,
+            recursive,
+            branch,
+            at_version,
+            jobs,
+            keep_going,
+        } => {
+            let options = CloneOptions {
+                force: *force,
+                recursive: *recursive,
+                branch: branch.clone(),
+                at_version: at_version.clone(),
+                jobs: jobs.or(config.jobs).unwrap_or(DEFAULT_JOBS),
+                keep_going: *keep_going,
+                no_confirm,
+                scheme: config.clone_scheme,
+            };
+            if let Err(e) = clone(proj_dirs, args.repos, package_names, options, &auth).await {
+                eprintln!("Error while cloning: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Comments {
+            package_name,
+            count,
+        } => {
+            if let Err(e) = show_comments(&proj_dirs, package_name, *count, &out).await {
+                eprintln!("Error while reading comments: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Complete { current, aur } => {
+            // Completion must never print errors into the user's command line
+            let _ = complete(proj_dirs, args.repos, current, *aur).await;
+        }
+        Command::Completions { shell } => {
+            print!("{}", completions::generate(*shell, Args::command()));
+        }
+        Command::Deps { package_name, tree } => {
+            if let Err(e) = deps(proj_dirs, args.repos, package_name, *tree, &out).await {
+                eprintln!("Error while resolving dependencies: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Diff {
+            package_name,
+            upstream_only,
+        } => {
+            // Fetching into a detached state leaves the repository untouched
+            if let Err(e) = diff(
+                proj_dirs,
+                args.repos,
+                package_name,
+                *upstream_only || dry_run,
+                &auth,
+                &out,
+            ) {
+                eprintln!("Error while diffing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Freeze { package_names } => {
+            if let Err(e) = freeze(&proj_dirs, package_names, true) {
+                eprintln!("Error while freezing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Thaw { package_names } => {
+            if let Err(e) = freeze(&proj_dirs, package_names, false) {
+                eprintln!("Error while thawing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Flag {
+            package_name,
+            comment,
+        } => {
+            let session = aur_session(&config);
+            if let Err(e) = flag(session.as_ref(), package_name, comment).await {
+                eprintln!("Error while flagging: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Fetch {
+            quiet,
+            max_commits,
+            exit_code,
+            ignore_errors,
+            stale,
+            status_format,
+            verify_signatures,
+            deep,
+            timings,
+            summary,
+            grep,
+            recursive_scan,
+            notify,
+        } => {
+            let options = FetchOptions {
+                quiet: *quiet,
+                exit_code: *exit_code,
+                ignore_errors: *ignore_errors,
+                status_format: *status_format,
+                summary: *summary,
+                timings: *timings,
+                grep: grep.clone(),
+                notify: *notify || config.notify,
+                check: CheckOptions {
+                    // The summary only needs the counts, which don't require listing commits
+                    max_commits: if *summary { Some(0) } else { *max_commits },
+                    stale: *stale,
+                    verify_signatures: *verify_signatures,
+                    deep: *deep,
+                    recursive_scan: *recursive_scan || config.recursive_scan,
+                    ..CheckOptions::default()
+                },
+            };
+            if let Err(e) = fetch(proj_dirs, args.repos, options, &auth, &out).await {
+                if *ignore_errors {
+                    eprintln!("Warning: Error while fetching: {}", e);
+                } else {
+                    eprintln!("Error while fetching: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Command::Get {
+            expression,
+            install,
+        } => {
+            let scheme = config.clone_scheme;
+            let install = install.then(|| BuildOptions {
+                no_confirm,
+                output_dir: output_dir.clone(),
+                tools: tools.clone(),
+                makepkg_args: config.makepkg_args(),
+                ..BuildOptions::default()
+            });
+            if let Err(e) = get(
+                proj_dirs, args.repos, expression, install, scheme, &auth, &out,
+            )
+            .await
+            {
+                eprintln!("Error while getting package: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Info {
+            package_names,
+            raw_json,
+            fields,
+        } => {
+            if let Err(e) = info(package_names, *raw_json, fields, &out).await {
+                eprintln!("Error while looking up packages: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::List {
+            sort_by,
+            porcelain,
+            fetch,
+        } => {
+            let options = ListOptions {
+                sort_by: *sort_by,
+                porcelain: *porcelain,
+                fetch: *fetch,
+            };
+            if let Err(e) = list(proj_dirs, args.repos, options, &auth, &out).await {
+                eprintln!("Error while listing repositories: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Tui => {
+            let options = BuildOptions {
+                no_confirm,
+                output_dir: output_dir.clone(),
+                tools: tools.clone(),
+                makepkg_args: config.makepkg_args(),
+                ..BuildOptions::default()
+            };
+            let hook = config.post_pull_hook.as_ref();
+            if let Err(e) = tui(proj_dirs, args.repos, options, hook, &auth, &out).await {
+                eprintln!("Error in the interactive view: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Pull {
+            package_names,
+            all,
+            require_signatures,
+            hook,
+            jobs,
+            keep_going,
+            recursive_scan,
+            skip_review,
+        } => {
+            let hook = hook.as_ref().or(config.post_pull_hook.as_ref());
+            let options = PullOptions {
+                all: *all,
+                recursive_scan: *recursive_scan || config.recursive_scan,
+                require_signatures: *require_signatures,
+                jobs: jobs.or(config.jobs).unwrap_or(DEFAULT_JOBS),
+                keep_going: *keep_going,
+                review: !skip_review,
+                no_confirm,
+            };
+            if let Err(e) = pull(
+                proj_dirs,
+                args.repos,
+                package_names,
+                options,
+                hook,
+                &auth,
+                &out,
+            )
+            .await
+            {
+                eprintln!("Error while pulling: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Login => {
+            if let Err(e) = login() {
+                eprintln!("Error while logging in: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Timer {
+            interval,
+            notify,
+            uninstall,
+        } => {
+            let result = match uninstall {
+                true => remove_timer(),
+                false => install_timer(&args, interval, *notify),
+            };
+            if let Err(e) = result {
+                eprintln!("Error while setting up the timer: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Adopt { keep_going } => {
+            let options = AdoptOptions {
+                clone: !dry_run,
+                no_confirm,
+                scheme: config.clone_scheme,
+                keep_going: *keep_going,
+            };
+            if let Err(e) = adopt(proj_dirs, args.repos, options, &tools.pacman, &auth).await {
+                eprintln!("Error while adopting: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Check => {
+            if let Err(e) = check(&tools.pacman, &out).await {
+                eprintln!("Error while checking installed packages: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Orphans { remove } => {
+            let remove = *remove && !dry_run;
+            let pacman = &tools.pacman;
+            if let Err(e) = orphans(proj_dirs, args.repos, remove, no_confirm, pacman, &out) {
+                eprintln!("Error while looking for orphans: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Prune => {
+            let pacman = &tools.pacman;
+            if let Err(e) = orphans(proj_dirs, args.repos, !dry_run, no_confirm, pacman, &out) {
+                eprintln!("Error while pruning: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Provides { package_name } => {
+            if let Err(e) = provides(proj_dirs, args.repos, package_name, &out).await {
+                eprintln!("Error while looking up dependents: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Order { package_names, all } => {
+            if let Err(e) = order(proj_dirs, args.repos, package_names, *all, &out) {
+                eprintln!("Error while ordering: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Build {
+            package_names,
+            chroot,
+            keep_going,
+        } => {
+            let chroot = match chroot_of(chroot, &config, &proj_dirs) {
+                Ok(chroot) => chroot,
+                Err(e) => {
+                    eprintln!("Error while building: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let options = BuildOptions {
+                no_confirm,
+                chroot,
+                output_dir: output_dir.clone(),
+                tools: tools.clone(),
+                makepkg_args: config.makepkg_args(),
+                ..BuildOptions::default()
+            };
+            if let Err(e) = build(
+                proj_dirs,
+                args.repos,
+                package_names,
+                options,
+                *keep_going,
+                &out,
+            )
+            .await
+            {
+                eprintln!("Error while building: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Install {
+            package_names,
+            chroot,
+            keep_going,
+            skip_review,
+        } => {
+            let chroot = match chroot_of(chroot, &config, &proj_dirs) {
+                Ok(chroot) => chroot,
+                Err(e) => {
+                    eprintln!("Error while installing: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let options = InstallOptions {
+                build: BuildOptions {
+                    no_confirm,
+                    chroot,
+                    output_dir: output_dir.clone(),
+                    tools: tools.clone(),
+                    makepkg_args: config.makepkg_args(),
+                    ..BuildOptions::default()
+                },
+                scheme: config.clone_scheme,
+                keep_going: *keep_going,
+                review: !skip_review,
+            };
+            if let Err(e) =
+                install(proj_dirs, args.repos, package_names, options, &auth, &out).await
+            {
+                eprintln!("Error while installing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Rebuild {
+            package_names,
+            all,
+            install,
+            jobs,
+            keep_going,
+        } => {
+            let options = RebuildOptions {
+                all: *all,
+                install: *install,
+                jobs: *jobs,
+                keep_going: *keep_going,
+                build: BuildOptions {
+                    no_confirm,
+                    rebuild: true,
+                    output_dir: output_dir.clone(),
+                    tools: tools.clone(),
+                    makepkg_args: config.makepkg_args(),
+                    ..BuildOptions::default()
+                },
+            };
+            if let Err(e) = rebuild(proj_dirs, args.repos, package_names, options, &out).await {
+                eprintln!("Error while rebuilding: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Clean {
+            package_names,
+            all,
+            no_gc,
+        } => {
+            let gc = !no_gc;
+            if let Err(e) = clean(proj_dirs, args.repos, package_names, *all, gc, no_confirm) {
+                eprintln!("Error while cleaning: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Search {
+            expression,
+            installed,
+            installed_only,
+            exact,
+            limit,
+            page,
+            width,
+            no_truncate,
+            offline,
+            by,
+            select,
+        } => {
+            // Piped output is left alone, as there is no terminal to fit on
+            let width = match width {
+                Some(width) => Some(*width),
+                None if *no_truncate || !termion::is_tty(&std::io::stdout()) => None,
+                None => termion::terminal_size().ok().map(|(cols, _)| cols as usize),
+            };
+            let marking = if *installed_only {
+                CloneMarking::Only
+            } else if *installed {
+                CloneMarking::Mark
+            } else {
+                CloneMarking::None
+            };
+            let options = SearchOptions {
+                marking,
+                exact: *exact,
+                limit: *limit,
+                page: *page,
+                width,
+                offline: *offline,
+                by: *by,
+                select: select.then_some(config.clone_scheme),
+            };
+            if let Err(e) = search(proj_dirs, args.repos, expression, options, &auth, &out).await {
+                eprintln!("Error while searching: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::SyncDb { force } => {
+            if let Err(e) = sync_db(&proj_dirs, *force).await {
+                eprintln!("Error while downloading the package database: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Which {
+            package_name,
+            verify,
+        } => {
+            if let Err(e) = which(proj_dirs, args.repos, package_name, *verify) {
+                eprintln!("Error while locating repository: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Edit { package_name, file } => {
+            if let Err(e) = edit(proj_dirs, args.repos, package_name, file) {
+                eprintln!("Error while editing '{}': {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Remove {
+            package_names,
+            keep_files,
+            uninstall,
+        } => {
+            let options = RemoveOptions {
+                keep_files: *keep_files,
+                uninstall: *uninstall,
+                no_confirm,
+            };
+            if let Err(e) = remove(proj_dirs, args.repos, package_names, options, &tools.pacman) {
+                eprintln!("Error while removing: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Show {
+            package_name,
+            file,
+            pager,
+        } => {
+            if let Err(e) = show(proj_dirs, args.repos, package_name, file, *pager) {
+                eprintln!("Error while showing '{}': {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Verify { package_name } => {
+            if let Err(e) = verify(proj_dirs, args.repos, package_name, &tools, &out) {
+                eprintln!("Error while verifying: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Vote { package_name } => {
+            let session = aur_session(&config);
+            if let Err(e) = vote(session.as_ref(), package_name).await {
+                eprintln!("Error while voting: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Upgrade {
+            package_names,
+            force,
+            chroot,
+            keep_going,
+            skip_review,
+        } => {
+            let chroot = match chroot_of(chroot, &config, &proj_dirs) {
+                Ok(chroot) => chroot,
+                Err(e) => {
+                    eprintln!("Error while upgrading: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let options = UpgradeOptions {
+                selected: package_names.clone(),
+                dry_run,
+                keep_going: *keep_going,
+                review: !skip_review,
+                recursive_scan: config.recursive_scan,
+                build: BuildOptions {
+                    no_confirm,
+                    rebuild: *force,
+                    chroot,
+                    output_dir: output_dir.clone(),
+                    tools: tools.clone(),
+                    makepkg_args: config.makepkg_args(),
+                    ..BuildOptions::default()
+                },
+            };
+            let hook = config.post_pull_hook.as_ref();
+            if let Err(e) = upgrade(proj_dirs, args.repos, options, hook, &auth, &out).await {
+                eprintln!("Error while upgrading: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Parses the command run without a subcommand from `default_command` in the config
+///
+/// The setting may also contain options of the command and global options, e.g.
+/// `fetch --quiet --output plain`. Returns the command and the parsed arguments holding the
+/// global options, which `merge_defaults` applies. `config` and the options selecting the
+/// config itself are rejected.
+pub(crate) fn default_command_of(config: &Config) -> Result<(Command, Args), Error> {
+    let line = config.default_command.as_deref().unwrap_or("fetch");
+    let args = std::iter::once("taur").chain(line.split_whitespace());
+
+    let reason = match Args::try_parse_from(args) {
+        Ok(mut args) => match args.command.take() {
+            Some(Command::Config(_)) => String::from("config can't be the default command"),
+            Some(_) if args.profile.is_some() => {
+                String::from("--profile selects the config, so it can't be set in it")
+            }
+            Some(_) if args.legacy_repos.is_some() => {
+                String::from("give the repos directory with --repos")
+            }
+            // The global options given with the command are returned as well
+            Some(command) => return Ok((command, args)),
+            None => String::from("unknown command"),
+        },
+        Err(e) => {
+            // Only keep the actual message, without the usage hints
+            let message = e.to_string();
+            let message = message.split("\n\n").next().unwrap_or_default();
+            let words: Vec<&str> = message.split_whitespace().collect();
+            words.join(" ").trim_start_matches("error: ").to_string()
+        }
+    };
+
+    Err(Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid default_command '{}': {}", line, reason),
+    ))
+}
+
+/// Applies the global options of `default_command` that are not given on the command line
+///
+/// Flags are combined, options given on the command line win. A repos directory or workspace
+/// given on the command line replaces both of the defaults, as they conflict.
+fn merge_defaults(args: &mut Args, defaults: Args) {
+    if args.repos.is_none() && args.workspace.is_none() {
+        args.repos = defaults.repos;
+        args.workspace = defaults.workspace;
+    }
+    let default_output = match defaults.json {
+        true => Some(OutputFormat::Json),
+        false => defaults.output,
+    };
+    args.output = args.output.or(default_output);
+    args.color = args.color.or(defaults.color);
+    args.no_confirm |= defaults.no_confirm;
+    args.dry_run |= defaults.dry_run;
+    args.makepkg_path = args.makepkg_path.take().or(defaults.makepkg_path);
+    args.pacman_path = args.pacman_path.take().or(defaults.pacman_path);
+    args.output_dir = args.output_dir.take().or(defaults.output_dir);
+}
+
+async fn clone(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: CloneOptions,
+    auth: &RemoteAuth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if package_names.len() > 1 && (options.branch.is_some() || options.at_version.is_some()) {
+        return Err(Box::new(Error::new(
+            ErrorKind::InvalidInput,
+            "--branch and --at-version only work with a single package",
+        )));
+    }
+
+    let raur = raur::Handle::new();
+    let info = rpc::info(&raur, package_names).await?;
+    let missing = info.missing;
+    if !missing.is_empty() {
+        for name in &missing {
+            let suggestions = suggest_packages(&raur, name).await;
+            if !suggestions.is_empty() {
+                eprintln!(
+                    "'{}' not found, did you mean: {}?",
+                    name,
+                    suggestions.join(", ")
+                );
+            }
+        }
+        let message = match missing.as_slice() {
+            [name] => format!("Package '{}' not found", name),
+            _ => format!("Packages not found: {}", missing.join(", ")),
+        };
+        return Err(Box::new(Error::new(ErrorKind::NotFound, message)));
+    }
+
+    // Split packages live in the repository of their package base
+    let mut bases: Vec<String> = Vec::new();
+    for name in package_names {
+        let base = info
+            .found
+            .iter()
+            .find(|pkg| &pkg.name == name)
+            .map_or(name.as_str(), |pkg| pkg.package_base.as_str());
+        if base != name {
+            println!("'{}' is built by '{}', cloning that instead", name, base);
+        }
+        if !bases.iter().any(|b| b == base) {
+            bases.push(base.to_string());
+        }
+    }
+
+    let repo_path = get_repo_path(proj_dirs, repos);
+    if !repo_path.exists() {
+        std::fs::create_dir_all(repo_path.as_ref())?;
+    }
+
+    let existing: Vec<PathBuf> = bases
+        .iter()
+        .map(|name| repo_path.join(name))
+        .filter(|path| path.exists())
+        .collect();
+    if options.force && !existing.is_empty() {
+        let question = match existing.as_slice() {
+            [path] => format!("Remove {:?} and clone it again?", path),
+            _ => format!(
+                "Remove {} existing repositories and clone them again?",
+                existing.len()
+            ),
+        };
+        if !confirm(&question, options.no_confirm)? {
+            println!("Clone aborted");
+            return Ok(());
+        }
+    }
+
+    let policy = FailurePolicy::new(options.keep_going);
+    let mut cloned: Vec<&str> = Vec::new();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for package_name in bases.iter().take_while(|_| policy.proceed()) {
+        let result = clone_repo(
+            &repo_path,
+            package_name,
+            options.force,
+            options.branch.as_deref(),
+            options.scheme,
+            auth,
+        )
+        .map_err(Box::from)
+        .and_then(|()| match &options.at_version {
+            Some(version) => checkout_version(&repo_path.join(package_name), package_name, version),
+            None => Ok(()),
+        });
+        match result {
+            Ok(()) => cloned.push(package_name),
+            Err(e) => {
+                policy.record_failure();
+                failed.push((format!("'{}'", package_name), e.to_string()));
+            }
+        }
+    }
+    let mut skipped = bases.len() - cloned.len() - failed.len();
+
+    // Only look for dependencies of the packages that were cloned
+    let found: Vec<&raur::Package> = info
+        .found
+        .iter()
+        .filter(|pkg| cloned.contains(&pkg.package_base.as_str()))
+        .collect();
+    if options.recursive {
+        let names: Vec<&String> = package_names
+            .iter()
+            .filter(|name| found.iter().any(|pkg| &&pkg.name == name))
+            .collect();
+        skipped += names.len();
+        for package_name in names.into_iter().take_while(|_| policy.proceed()) {
+            skipped -= 1;
+            if let Err(e) =
+                clone_dependencies(&repo_path, package_name, options.clone(), auth).await
+            {
+                policy.record_failure();
+                failed.push((
+                    format!("the AUR dependencies of '{}'", package_name),
+                    e.to_string(),
+                ));
+            }
+        }
+    } else if !found.is_empty() {
+        let found: Vec<raur::Package> = found.into_iter().cloned().collect();
+        offer_dependencies(&raur, &repo_path, &found, &options, auth).await?;
+    }
+
+    if !failed.is_empty() {
+        for (what, reason) in &failed {
+            eprintln!("Error while cloning {}: {}", what, reason);
+        }
+        let what: Vec<&str> = failed.iter().map(|(what, _)| what.as_str()).collect();
+        return Err(Box::new(
+            policy.error(format!("Could not clone {}", what.join(", ")), skipped),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Points out AUR dependencies of freshly cloned packages that are not cloned yet, offering to
+/// clone them (and their own AUR dependencies) on a terminal
+///
+/// Failing to look up the dependencies only costs the hint, the packages are cloned anyway.
+async fn offer_dependencies(
+    raur: &raur::Handle,
+    repo_path: &Path,
+    packages: &[raur::Package],
+    options: &CloneOptions,
+    auth: &RemoteAuth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cloned = get_cloned_packages(repo_path)?;
+    let interactive = !options.no_confirm && termion::is_tty(&std::io::stdin());
+
+    for pkg in packages {
+        let deps: Vec<String> = pkg
+            .depends
+            .iter()
+            .chain(pkg.make_depends.iter())
+            .chain(pkg.check_depends.iter())
+            .map(|dep| srcinfo::strip_version(dep).to_string())
+            .collect();
+        if deps.is_empty() {
+            continue;
+        }
+        let found = match rpc::info(raur, &deps).await {
+            Ok(info) => info.found,
+            Err(_) => continue,
+        };
+
+        let mut missing: Vec<String> = found
+            .into_iter()
+            .map(|dep| dep.package_base)
+            .filter(|base| !cloned.contains(&OsString::from(base)))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        if missing.is_empty() {
+            continue;
+        }
+
+        println!(
+            "'{}' depends on AUR packages that are not cloned: {}",
+            pkg.name,
+            missing.join(", ")
+        );
+        if interactive && confirm("Clone them and their AUR dependencies?", false)? {
+            clone_dependencies(repo_path, &pkg.name, options.clone(), auth).await?;
+        } else {
+            println!("Clone them with 'taur clone {}'", missing.join(" "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns up to three AUR packages with names close to a package that doesn't exist
+///
+/// Besides the name itself, its first part (like `foo` of `foo-gti`) is searched, so misspelled
+/// suffixes are found as well. Failing or slow searches just give no suggestions.
+async fn suggest_packages(raur: &raur::Handle, package_name: &str) -> Vec<String> {
+    let mut queries = vec![package_name];
+    if let Some((first, _)) = package_name.split_once('-') {
+        queries.push(first);
+    }
+
+    let mut candidates: Vec<String> = Vec::new();
+    for query in queries {
+        // AUR refuses searches for less than two characters
+        if query.chars().count() < 2 {
+            continue;
+        }
+
+        let search = raur.search_by(query, SearchBy::Name);
+        if let Ok(Ok(pkgs)) = tokio::time::timeout(Duration::from_secs(3), search).await {
+            candidates.extend(pkgs.into_iter().map(|pkg| pkg.name));
+        }
+    }
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let limit = (package_name.chars().count() / 2).max(4);
+    let mut ranked: Vec<(usize, String)> = candidates
+        .into_iter()
+        .map(|name| (edit_distance(package_name, &name), name))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    ranked.sort_unstable();
+
+    ranked.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Returns the Levenshtein distance: how many characters have to be inserted, removed or
+/// replaced to turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Resets the checked out branch to the newest commit whose `.SRCINFO` has the given version
+///
+/// The version is either the full one (`1.2-3`, with the epoch if there is one) or only the
+/// pkgver. The branch stays behind its upstream, so fetch shows the newer commits.
+fn checkout_version(
+    repo_dir: &Path,
+    package_name: &str,
+    version: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = Repository::open(repo_dir)?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+
+    let mut seen: Vec<String> = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let entry = match tree.get_name(".SRCINFO") {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let blob = entry.to_object(&repo)?.peel_to_blob()?;
+        let srcinfo = Srcinfo::parse(&String::from_utf8_lossy(blob.content()));
+        let full = match srcinfo.version() {
+            Some(full) => full,
+            None => continue,
+        };
+
+        if full == version || srcinfo.pkgver.as_deref() == Some(version) {
+            repo.reset(commit.as_object(), git2::ResetType::Hard, None)?;
+            println!(
+                "Checked out '{}' at version {} ({})",
+                package_name,
+                full,
+                &commit.id().to_string()[..7]
+            );
+            return Ok(());
+        }
+        if !seen.contains(&full) {
+            seen.push(full);
+        }
+    }
+
+    Err(Box::new(Error::new(
+        ErrorKind::NotFound,
+        format!(
+            "Version '{}' not found in the history of '{}', it was left at the latest version \
+             (available: {})",
+            version,
+            package_name,
+            seen.join(", ")
+        ),
+    )))
+}
+
+/// Clones the AUR dependencies of the given package that are not cloned yet
+async fn clone_dependencies(
+    repo_path: &Path,
+    package_name: &str,
+    options: CloneOptions,
+    auth: &RemoteAuth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let graph = depgraph::resolve(package_name).await?;
+    let cloned = get_cloned_packages(repo_path)?;
+
+    let missing: BTreeSet<&String> = graph
+        .package_bases
+        .values()
+        .filter(|base| !cloned.contains(&OsString::from(base)))
+        .collect();
+
+    let total = missing.len();
+    let policy = FailurePolicy::new(options.keep_going);
+    let clones = missing
+        .into_iter()
+        .take_while(|_| policy.proceed())
+        .map(|base| {
+            let repo_path = repo_path.to_path_buf();
+            let base = base.clone();
+            let scheme = options.scheme;
+            let auth = auth.clone();
+            task::spawn_blocking(move || {
+                let result = clone_repo(&repo_path, &base, false, None, scheme, &auth);
+                (base, result)
+            })
+        });
+
+    let mut succeeded: Vec<String> = Vec::new();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    let mut results = futures::stream::iter(clones).buffer_unordered(options.jobs.max(1));
+    while let Some(joined) = results.next().await {
+        let (base, result) = joined.map_err(Error::other)?;
+        match result {
+            Ok(()) => succeeded.push(base),
+            Err(e) => {
+                policy.record_failure();
+                failed.push((base, e.to_string()));
+            }
+        }
+    }
+    drop(results);
+    succeeded.sort_unstable();
+    failed.sort_unstable();
+
+    println!();
+    if succeeded.is_empty() {
+        println!(
+            "All AUR dependencies of '{}' were already cloned",
+            package_name
+        );
+    } else {
+        println!(
+            "Cloned AUR dependencies of '{}': {}",
+            package_name,
+            succeeded.join(", ")
+        );
+    }
+
+    if !failed.is_empty() {
+        for (base, reason) in &failed {
+            eprintln!("Error while cloning '{}': {}", base, reason);
+        }
+        let skipped = total - succeeded.len() - failed.len();
+        return Err(Box::new(policy.error(
+            format!(
+                "{} {} could not be cloned",
+                failed.len(),
+                repositories(failed.len())
+            ),
+            skipped,
+        )));
+    }
+
+    Ok(())
+}
+
+/// Makes sure the package can be cloned to `repo_path`, which must not exist or be empty
+///
+/// With `force`, an existing directory is removed first.
+fn prepare_clone_target(repo_path: &Path, package_name: &str, force: bool) -> Result<(), Error> {
+    // libgit2 happily clones into an empty directory, e.g. one the user created beforehand
+    let is_empty_dir = std::fs::read_dir(repo_path).is_ok_and(|mut dir| dir.next().is_none());
+    if !repo_path.exists() || is_empty_dir {
+        return Ok(());
+    }
+
+    if force {
+        // Never follow a symlink out of the repos directory when deleting
+        if !std::fs::symlink_metadata(repo_path)?.is_dir() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{:?} is not a directory, refusing to remove it", repo_path),
+            ));
+        }
+        std::fs::remove_dir_all(repo_path)?;
+        println!("Removed existing directory {:?}", repo_path);
+        Ok(())
+    } else if Repository::open(repo_path).is_ok() {
+        Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!(
+                "Repository '{}' was already cloned, use 'taur pull {}' to update it",
+                package_name, package_name
+            ),
+        ))
+    } else {
+        Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!(
+                "{:?} exists but is no valid repository, use 'taur clone --force {}' to \
+                 clone it again",
+                repo_path, package_name
+            ),
+        ))
+    }
+}
+
+/// Clones a single AUR repository into the repository directory
+///
+/// With a `branch`, that branch is checked out and tracked instead of the remote's default.
+fn clone_repo(
+    repo_path: &Path,
+    package_name: &str,
+    force: bool,
+    branch: Option<&str>,
+    scheme: CloneScheme,
+    auth: &RemoteAuth,
+) -> Result<(), Error> {
+    let repo_path = repo_path.join(package_name);
+    prepare_clone_target(&repo_path, package_name, force)?;
+
+    let url = scheme.url(package_name);
+
+    let mut builder = git2::build::RepoBuilder::new();
+    builder.fetch_options(auth.fetch_options());
+    if let Some(branch) = branch {
+        builder.branch(branch);
+    }
+
+    match builder.clone(&url, &repo_path) {
+        Ok(_) => println!("Cloned repo '{}' to '{:?}'", package_name, repo_path),
+        Err(e) if branch.is_some() && e.code() == ErrorCode::NotFound => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "Branch '{}' does not exist in the repository of '{}'",
+                    branch.unwrap_or_default(),
+                    package_name
+                ),
+            ))
+        }
+        Err(e) => {
+            return Err(Error::other(format!(
+                "Error while cloning repo '{}': {}",
+                package_name,
+                remote::explain(e)
+            )))
+        }
+    };
+
+    Ok(())
+}
+
+/// Prints cloned repositories (or AUR packages) whose name starts with `current`
+async fn complete(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    current: &str,
+    aur: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut names: Vec<String> = if aur {
+        // AUR refuses searches for less than two characters
+        if current.chars().count() < 2 {
+            return Ok(());
+        }
+
+        // Better no suggestions than a shell that hangs on a slow connection
+        let raur = raur::Handle::new();
+        let search = raur.search_by(current, SearchBy::Name);
+        tokio::time::timeout(Duration::from_secs(2), search)
+            .await??
+            .into_iter()
+            .map(|pkg| pkg.name)
+            .collect()
+    } else {
+        get_cloned_packages(&get_repo_path(proj_dirs, repos))?
+            .into_iter()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .collect()
+    };
+
+    names.retain(|name| name.starts_with(current));
+    names.sort_unstable();
+    for name in names {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+/// Fields shown by `info`, as named for `--field` and as labeled in the output
+const INFO_FIELDS: [(&str, &str); 20] = [
+    ("name", "Name"),
+    ("package-base", "Package Base"),
+    ("version", "Version"),
+    ("description", "Description"),
+    ("url", "URL"),
+    ("aur-url", "AUR URL"),
+    ("licenses", "Licenses"),
+    ("keywords", "Keywords"),
+    ("provides", "Provides"),
+    ("depends", "Depends On"),
+    ("make-depends", "Make Deps"),
+    ("check-depends", "Check Deps"),
+    ("optional-depends", "Optional Deps"),
+    ("conflicts", "Conflicts With"),
+    ("maintainer", "Maintainer"),
+    ("votes", "Votes"),
+    ("popularity", "Popularity"),
+    ("out-of-date", "Out Of Date"),
+    ("first-submitted", "First Submitted"),
+    ("last-modified", "Last Modified"),
+];
+
+/// Returns the value of one of the `INFO_FIELDS`, joining lists with the separator
+fn info_field(pkg: &raur::Package, field: &str, separator: &str) -> String {
+    match field {
+        "name" => pkg.name.clone(),
+        "package-base" => pkg.package_base.clone(),
+        "version" => pkg.version.clone(),
+        "description" => pkg.description.clone().unwrap_or_default(),
+        "url" => pkg.url.clone().unwrap_or_default(),
+        "aur-url" => format!("https://{}/packages/{}", remote::AUR_HOST, pkg.name),
+        "licenses" => pkg.license.join(separator),
+        "keywords" => pkg.keywords.join(separator),
+        "provides" => pkg.provides.join(separator),
+        "depends" => pkg.depends.join(separator),
+        "make-depends" => pkg.make_depends.join(separator),
+        "check-depends" => pkg.check_depends.join(separator),
+        "optional-depends" => pkg.opt_depends.join(separator),
+        "conflicts" => pkg.conflicts.join(separator),
+        "maintainer" => pkg.maintainer.clone().unwrap_or_default(),
+        "votes" => pkg.num_votes.to_string(),
+        "popularity" => format!("{:.2}", pkg.popularity),
+        "out-of-date" => match pkg.out_of_date {
+            Some(_) => String::from("Yes"),
+            None => String::from("No"),
+        },
+        "first-submitted" => format_timestamp(pkg.first_submitted),
+        "last-modified" => format_timestamp(pkg.last_modified),
+        _ => String::new(),
+    }
+}
+
+/// Formats a Unix timestamp of the AUR as an age, like "3 days ago"
+fn format_timestamp(timestamp: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or_default();
+    format_age(now.saturating_sub(timestamp).max(0) as u64)
+}
+
+async fn info(
+    package_names: &[String],
+    raw_json: bool,
+    fields: &[String],
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let names: Vec<&str> = INFO_FIELDS.iter().map(|(name, _)| *name).collect();
+    if let Some(unknown) = fields.iter().find(|field| !names.contains(&field.as_str())) {
+        return Err(Box::new(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown field '{}' (known: {})", unknown, names.join(", ")),
+        )));
+    }
+
+    let raur = raur::Handle::new();
+    let results = rpc::info(&raur, package_names).await?;
+    let mut pkgs = results.found;
+
+    // Keep the order the packages were asked for
+    pkgs.sort_by_key(|pkg| package_names.iter().position(|name| *name == pkg.name));
+
+    if raw_json || (out.is_json() && fields.is_empty()) {
+        out.json(&pkgs)?;
+        return Ok(());
+    }
+
+    for pkg in &pkgs {
+        if !fields.is_empty() {
+            // Plain values only, so they can be captured in a shell variable
+            for field in fields {
+                println!("{}", info_field(pkg, field, " "));
+            }
+            continue;
+        }
+
+        for (field, label) in INFO_FIELDS {
+            let value = info_field(pkg, field, "  ");
+            let value = if value.is_empty() {
+                String::from("None")
+            } else {
+                value
+            };
+            println!(
+                "{}{:<15}{}: {}",
+                out.paint(style::Bold),
+                label,
+                out.paint(style::Reset),
+                value
+            );
+        }
+        println!();
+    }
+
+    if !results.missing.is_empty() {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("Packages not found: {}", results.missing.join(", ")),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Searches AUR and clones (and optionally installs) the package the user picks
+///
+/// Without a terminal to ask on, the expression has to match a package name exactly.
+async fn get(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    expression: &str,
+    install: Option<BuildOptions>,
+    scheme: CloneScheme,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(build_options) = &install {
+        build_options.check()?;
+    }
+
+    let raur = raur::Handle::new();
+    let mut pkgs = error::retry(|| raur.search(expression)).await?;
+    pkgs.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    if pkgs.is_empty() {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("No packages found for '{}'", expression),
+        )));
+    }
+
+    let interactive = termion::is_tty(&std::io::stdin()) && termion::is_tty(&std::io::stdout());
+    let package_name = if interactive {
+        for (i, pkg) in pkgs.iter().enumerate() {
+            println!(
+                "{:>3} {}{}{} {}",
+                i + 1,
+                out.fg(out.theme.package_name),
+                pkg.name,
+                out.paint(style::Reset),
+                pkg.version
+            );
+            if let Some(description) = &pkg.description {
+                println!("    {}", description);
+            }
+        }
+
+        match select(pkgs.len())? {
+            Some(index) => pkgs.swap_remove(index).name,
+            None => {
+                println!("Nothing selected");
+                return Ok(());
+            }
+        }
+    } else {
+        match pkgs.into_iter().find(|pkg| pkg.name == expression) {
+            Some(pkg) => pkg.name,
+            None => {
+                return Err(Box::new(Error::new(
+                    ErrorKind::NotFound,
+                    format!(
+                        "No package named exactly '{}' (selecting from the search results \
+                         requires a terminal)",
+                        expression
+                    ),
+                )))
+            }
+        }
+    };
+
+    let repo_path = get_repo_path(proj_dirs.clone(), repos.clone());
+    let options = CloneOptions {
+        scheme,
+        ..CloneOptions::default()
+    };
+    clone(
+        proj_dirs,
+        repos,
+        std::slice::from_ref(&package_name),
+        options,
+        auth,
+    )
+    .await?;
+
+    if let Some(build_options) = install {
+        task::spawn_blocking(move || {
+            let files = build_cloned(&repo_path, &package_name, &build_options)?;
+            build::install_packages(&files, &build_options)
+        })
+        .await??;
+    }
+
+    Ok(())
+}
+
+/// Builds a freshly cloned package, which split packages were cloned as their package base for
+fn build_cloned(
+    repo_path: &Path,
+    package_name: &str,
+    build_options: &BuildOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    let name = cloned_repo(repo_path, package_name)?;
+    let extra_args = build_options.makepkg_args(&name);
+    build::build_package(&repo_path.join(&name), build_options, &[], extra_args)
+}
+
+/// Asks for a number between 1 and `count`, returning the matching index
+///
+/// Returns None if the answer is empty.
+fn select(count: usize) -> Result<Option<usize>, Error> {
+    loop {
+        print!("Select a package (1-{}, empty to abort): ", count);
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => return Ok(Some(number - 1)),
+            _ => println!("Invalid selection '{}'", answer),
+        }
+    }
+}
+
+/// Asks for any number of packages between 1 and `count`, returning the matching indices
+///
+/// Numbers are separated by spaces or commas, and ranges like `2-4` pick all packages in
+/// between. Returns no indices if the answer is empty.
+fn select_many(count: usize) -> Result<Vec<usize>, Error> {
+    loop {
+        print!("Packages to clone (e.g. 1 3 or 2-4, empty to abort): ");
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(Vec::new());
+        }
+
+        match parse_selection(answer.trim(), count) {
+            Some(indices) => return Ok(indices),
+            None => println!(
+                "Invalid selection '{}' (numbers from 1 to {})",
+                answer.trim(),
+                count
+            ),
+        }
+    }
+}
+
+/// Parses numbers and ranges of numbers between 1 and `count` into sorted, unique indices
+fn parse_selection(answer: &str, count: usize) -> Option<Vec<usize>> {
+    let mut indices = BTreeSet::new();
+    for part in answer.split([' ', ',']).filter(|part| !part.is_empty()) {
+        let (first, last): (usize, usize) = match part.split_once('-') {
+            Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+            None => {
+                let number = part.parse().ok()?;
+                (number, number)
+            }
+        };
+        if first < 1 || first > last || last > count {
+            return None;
+        }
+        indices.extend(first - 1..last);
+    }
+
+    Some(indices.into_iter().collect())
+}
+
+/// Returns the programs to run, given by flags, the config or found on the `PATH`
+fn tools_of(args: &Args, config: &Config) -> Tools {
+    let defaults = Tools::default();
+    Tools {
+        makepkg: args
+            .makepkg_path
+            .clone()
+            .or_else(|| config.makepkg_path.clone())
+            .unwrap_or(defaults.makepkg),
+        pacman: args
+            .pacman_path
+            .clone()
+            .or_else(|| config.pacman_path.clone())
+            .unwrap_or(defaults.pacman),
+    }
+}
+
+/// Returns the repos directory given by flag, workspace or the config, or None for the
+/// default one
+///
+/// A profile keeps its own repositories, so $AURDEST (shared with other helpers) is ignored.
+fn repos_of(
+    args: &Args,
+    proj_dirs: &ProjectDirs,
+    config: &Config,
+) -> Result<Option<PathBuf>, Error> {
+    if let Some(name) = &args.workspace {
+        return config.workspace(name).map(Some);
+    }
+
+    Ok(args
+        .repos
+        .clone()
+        .or_else(|| config.repos.clone())
+        .or_else(|| {
+            args.profile
+                .as_ref()
+                .map(|_| proj_dirs.data_dir().join("repos"))
+        }))
+}
+
+/// Checks everything taur depends on and prints the results as a checklist
+///
+/// Only failures make the command fail, warnings are about things some commands don't need.
+async fn doctor(
+    proj_dirs: &ProjectDirs,
+    args: &Args,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (config_check, config) = doctor::config(proj_dirs);
+    // The remaining checks go on with the defaults if the config is broken
+    let config = config.unwrap_or_default();
+    let tools = tools_of(args, &config);
+    let repo_path = get_repo_path(proj_dirs.clone(), repos_of(args, proj_dirs, &config)?);
+
+    let mut checks = vec![config_check, doctor::repos_dir(&repo_path)];
+    checks.push(doctor::program(
+        Path::new("git"),
+        "git",
+        false,
+        "Install git (pacman -S git), makepkg needs it for sources from git repositories",
+    ));
+    checks.push(doctor::program(
+        &tools.makepkg,
+        "makepkg",
+        true,
+        "Install pacman, which includes makepkg, or set `makepkg_path` in the config",
+    ));
+    checks.push(doctor::program(
+        &tools.pacman,
+        "pacman",
+        true,
+        "Install pacman or set `pacman_path` in the config",
+    ));
+    checks.push(doctor::program(
+        Path::new("gpg"),
+        "gpg",
+        false,
+        "Install gnupg (pacman -S gnupg), needed for --verify-signatures, \
+         pull --require-signatures and signed sources",
+    ));
+    checks.extend(doctor::ssh(&config));
+    checks.push(doctor::aur(raur::Handle::new().client()).await);
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == doctor::Status::Fail)
+        .count();
+
+    if out.is_json() {
+        out.json(&checks)?;
+    } else {
+        print_checks(out, &checks);
+    }
+
+    if failed > 0 {
+        return Err(Box::new(Error::other(format!(
+            "{} {} failed",
+            failed,
+            if failed == 1 { "check" } else { "checks" }
+        ))));
+    }
+
+    Ok(())
+}
+
+fn print_checks(out: &Formatter, checks: &[doctor::Check]) {
+    for check in checks {
+        let (marker, color) = match check.status {
+            doctor::Status::Pass => ("[ ok ]", out.fg(out.theme.success)),
+            doctor::Status::Warn => ("[warn]", out.fg(out.theme.warning)),
+            doctor::Status::Fail => (
+                "[fail]",
+                out.fg(out.theme.warning) + &out.paint(style::Bold),
+            ),
+        };
+        println!(
+            "{}{}{} {}: {}",
+            color,
+            marker,
+            out.paint(style::Reset),
+            check.name,
+            // Line up multi-line details like parse errors below the first line
+            check.detail.trim_end().replace('\n', "\n       ")
+        );
+        if let Some(remedy) = &check.remedy {
+            println!("       {}", remedy);
+        }
+    }
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let (warnings, failures) = (count(doctor::Status::Warn), count(doctor::Status::Fail));
+    println!();
+    if warnings == 0 && failures == 0 {
+        println!("Everything looks fine");
+    } else {
+        println!(
+            "{} {}, {} {}",
+            failures,
+            if failures == 1 { "failure" } else { "failures" },
+            warnings,
+            if warnings == 1 { "warning" } else { "warnings" }
+        );
+    }
+}
+
+fn configure(proj_dirs: &ProjectDirs, action: &ConfigAction, dry_run: bool) -> Result<(), Error> {
+    match action {
+        ConfigAction::Set { key, value } if dry_run => {
+            eprintln!("(dry run)");
+            println!(
+                "Would set {} to '{}' in {:?}",
+                key,
+                value,
+                Config::path(proj_dirs)
+            );
+        }
+        ConfigAction::Get { key } => match Config::load(proj_dirs)?.get(key)? {
+            Some(toml::Value::String(value)) => println!("{}", value),
+            Some(value) => println!("{}", value),
+            None => println!("(not set)"),
+        },
+        ConfigAction::Set { key, value } => Config::set(proj_dirs, key, value)?,
+        ConfigAction::Path => println!("{}", Config::path(proj_dirs).display()),
+    }
+
+    Ok(())
+}
+
+fn verify(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    tools: &Tools,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs, repos);
+    let path = repo_path.join(cloned_repo(&repo_path, package_name)?);
+    build::check_executable(&tools.makepkg, "makepkg")?;
+
+    let srcinfo = Srcinfo::read(&path).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Unable to read .SRCINFO of '{}': {}", package_name, e),
+        )
+    })?;
+    let report = checksums::check(&srcinfo);
+    for name in &report.skipped {
+        println!(
+            "{}Not checked:{} {} (its checksum is SKIP)",
+            out.fg(out.theme.warning),
+            out.paint(style::Reset),
+            name
+        );
+    }
+    for problem in &report.problems {
+        println!(
+            "{}Checksums:{} {}",
+            out.fg(out.theme.warning),
+            out.paint(style::Reset),
+            problem
+        );
+    }
+    if !report.problems.is_empty() {
+        return Err(Box::new(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The checksums of '{}' are incomplete, not downloading its sources",
+                package_name
+            ),
+        )));
+    }
+
+    build::verify_sources(&path, tools)?;
+    println!(
+        "{}The sources of '{}' match their checksums{}",
+        out.fg(out.theme.success),
+        package_name,
+        out.paint(style::Reset)
+    );
+
+    Ok(())
+}
+
+fn which(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    verify: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs, repos);
+    let path = repo_path.join(cloned_repo(&repo_path, package_name)?);
+
+    if verify {
+        Repository::open(&path).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{:?} is no valid repository: {}", path, e.message()),
+            )
+        })?;
+    }
+
+    println!("{}", std::fs::canonicalize(&path)?.display());
+
+    Ok(())
+}
+
+/// Deletes the build artifacts of the given repositories after confirming, then runs `git gc`
+/// in them
+///
+/// Files tracked by git are never deleted, even if their names look like build artifacts.
+fn clean(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    all: bool,
+    gc: bool,
+    no_confirm: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    let names = select_repos(&proj_dirs, &repo_path, package_names, all, false, "clean")?;
+
+    let mut artifacts: Vec<(&str, Vec<PathBuf>)> = Vec::new();
+    let mut total = 0;
+    for name in &names {
+        let path = repo_path.join(name);
+        let tracked = tracked_entries(&Repository::open(&path)?)?;
+        let files: Vec<PathBuf> = build::artifacts(&path)?
+            .into_iter()
+            .filter(|file| {
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                !tracked.contains(name.as_ref())
+            })
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        let size: u64 = files.iter().map(|file| disk_usage(file)).sum();
+        total += size;
+        let list: Vec<String> = files
+            .iter()
+            .map(|file| {
+                file.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        println!("{}: {} ({})", name, list.join(", "), format_size(size));
+        artifacts.push((name, files));
+    }
+
+    if artifacts.is_empty() {
+        println!("No build artifacts found");
+    } else {
+        let question = format!(
+            "Delete the build artifacts of {} {} ({})?",
+            artifacts.len(),
+            repositories(artifacts.len()),
+            format_size(total)
+        );
+        if confirm(&question, no_confirm)? {
+            for (_, files) in &artifacts {
+                for file in files {
+                    match file.is_dir() {
+                        true => std::fs::remove_dir_all(file)?,
+                        false => std::fs::remove_file(file)?,
+                    }
+                }
+            }
+            println!("Deleted {}", format_size(total));
+        } else {
+            println!("Nothing deleted");
+        }
+    }
+
+    if !gc {
+        return Ok(());
+    }
+
+    let mut saved = 0;
+    for name in &names {
+        let git_dir = repo_path.join(name).join(".git");
+        let before = disk_usage(&git_dir);
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(repo_path.join(name))
+            .args(["gc", "--quiet"])
+            .status()
+            .map_err(|e| Error::new(e.kind(), format!("Unable to run git: {}", e)))?;
+        if !status.success() {
+            eprintln!("Warning: git gc failed in '{}': {}", name, status);
+            continue;
+        }
+        saved += before.saturating_sub(disk_usage(&git_dir));
+    }
+    println!(
+        "Ran git gc in {} {}, saving {}",
+        names.len(),
+        repositories(names.len()),
+        format_size(saved)
+    );
+
+    Ok(())
+}
+
+/// Returns the top-level files and directories of the repository that contain tracked files
+fn tracked_entries(repo: &Repository) -> Result<HashSet<String>, git2::Error> {
+    Ok(repo
+        .index()?
+        .iter()
+        .map(|entry| {
+            let path = String::from_utf8_lossy(&entry.path);
+            path.split('/').next().unwrap_or_default().to_string()
+        })
+        .collect())
+}
+
+/// Returns the size of a file, or of all files inside a directory, without following symlinks
+fn disk_usage(path: &Path) -> u64 {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+/// How `remove` stops tracking packages
+#[derive(Clone, Copy)]
+struct RemoveOptions {
+    /// Only add the packages to .taurignore instead of deleting their repositories
+    keep_files: bool,
+    /// Uninstall the packages without offering it
+    uninstall: bool,
+    no_confirm: bool,
+}
+
+/// Deletes the repositories of the given packages after confirming, or only ignores them with
+/// `keep_files`, then offers to uninstall the packages built from them
+fn remove(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: RemoveOptions,
+    pacman: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+
+    let mut names = Vec::new();
+    for package_name in package_names {
+        let name = cloned_repo(&repo_path, package_name)?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    // Split packages install several packages, read them before the repositories are gone
+    let mut pkgnames: Vec<String> = Vec::new();
+    for name in &names {
+        match Srcinfo::read(&repo_path.join(name)) {
+            Ok(srcinfo) if !srcinfo.pkgnames.is_empty() => pkgnames.extend(srcinfo.pkgnames),
+            _ => pkgnames.push(name.clone()),
+        }
+    }
+
+    if options.keep_files {
+        let ignored = TaurIgnore::load(&repo_path)?;
+        for name in &names {
+            if ignored.is_ignored(name) {
+                println!("'{}' is already ignored", name);
+            } else {
+                TaurIgnore::add(&repo_path, name)?;
+                println!("Added '{}' to {:?}", name, repo_path.join(".taurignore"));
+            }
+        }
+    } else {
+        let question = match names.as_slice() {
+            [name] => format!("Delete {:?}?", repo_path.join(name)),
+            _ => format!("Delete {} repositories?", names.len()),
+        };
+        if !confirm(&question, options.no_confirm)? {
+            println!("Nothing removed");
+            return Ok(());
+        }
+
+        let mut frozen = Frozen::load(&proj_dirs)?;
+        for name in &names {
+            std::fs::remove_dir_all(repo_path.join(name))?;
+            frozen.thaw(name);
+            println!("Removed {}", name);
+        }
+        frozen.save(&proj_dirs)?;
+    }
+
+    // Only offer uninstalling where someone can answer, --no-confirm alone never uninstalls
+    let interactive = !options.no_confirm && termion::is_tty(&std::io::stdin());
+    if !options.uninstall && !interactive {
+        return Ok(());
+    }
+
+    // Without pacman there is nothing to offer, which is only an error if it was asked for
+    if let Err(e) = build::check_executable(pacman, "pacman") {
+        return match options.uninstall {
+            true => Err(Box::new(e)),
+            false => Ok(()),
+        };
+    }
+    let installed = build::installed_packages(false, pacman)?;
+    pkgnames.retain(|pkg| installed.contains(pkg));
+    if pkgnames.is_empty() {
+        return Ok(());
+    }
+
+    if !options.uninstall {
+        let question = format!("Uninstall {} with pacman -R?", pkgnames.join(", "));
+        if !confirm(&question, false)? {
+            return Ok(());
+        }
+    }
+
+    Ok(build::uninstall_packages(
+        &pkgnames,
+        options.no_confirm,
+        pacman,
+    )?)
+}
+
+/// Opens a file of a cloned package in the editor, protecting the changes from pull
+fn edit(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs, repos);
+    let name = cloned_repo(&repo_path, package_name)?;
+    let path = repo_path.join(&name);
+    if !path.join(file).is_file() {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("'{}' has no file '{}'", package_name, file),
+        )));
+    }
+
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+    // The editor may come with options, like "code --wait"
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(file)
+        .current_dir(&path)
+        .status()?;
+    if !status.success() {
+        return Err(Box::new(Error::other(format!(
+            "{} failed: {}",
+            editor, status
+        ))));
+    }
+
+    let repo = Repository::open(&path)?;
+    let changed = changed_files(&repo)?;
+    repo.config()?.set_bool(EDITED_KEY, !changed.is_empty())?;
+    if !changed.is_empty() {
+        println!(
+            "'{}' has local changes to {}, pull won't overwrite them",
+            name,
+            changed.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Key in a repository's git config marking that it was changed with `taur edit`
+const EDITED_KEY: &str = "taur.edited";
+
+/// Returns the tracked files of the repository that differ from HEAD
+fn changed_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(String::from))
+        .collect())
+}
+
+/// Prints a file of a cloned package as committed at HEAD, optionally through a pager
+fn show(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    file: &str,
+    pager: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_path = get_repo_path(proj_dirs, repos);
+    let name = cloned_repo(&repo_path, package_name)?;
+
+    let repo = Repository::open(repo_path.join(name))?;
+    let tree = repo.head()?.peel_to_tree()?;
+    let blob = tree
+        .get_path(Path::new(file))
+        .and_then(|entry| entry.to_object(&repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|_| {
+            let files: Vec<String> = tree
+                .iter()
+                .filter(|entry| entry.kind() == Some(git2::ObjectType::Blob))
+                .filter_map(|entry| entry.name().map(String::from))
+                .collect();
+            Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "'{}' has no such file (files: {})",
+                    package_name,
+                    files.join(", ")
+                ),
+            )
+        })?;
+
+    if pager && termion::is_tty(&std::io::stdout()) {
+        page(file, blob.content())?;
+    } else {
+        std::io::stdout().write_all(blob.content())?;
+    }
+
+    Ok(())
+}
+
+/// Shows the content in bat, highlighted by the file name, or in $PAGER (less by default)
+fn page(file_name: &str, content: &[u8]) -> Result<(), Error> {
+    let bat = std::process::Command::new("bat")
+        .args(["--paging=always", "--file-name", file_name])
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    let mut child = match bat {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // $PAGER may come with options, like "less -R"
+            let pager = std::env::var("PAGER")
+                .ok()
+                .filter(|pager| !pager.trim().is_empty())
+                .unwrap_or_else(|| String::from("less"));
+            std::process::Command::new("sh")
+                .arg("-c")
+                .arg(pager)
+                .stdin(std::process::Stdio::piped())
+                .spawn()?
+        }
+        Err(e) => return Err(e),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may be quit before reading everything
+        match stdin.write_all(content) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+
+    Ok(())
+}
+
+/// Changes between a local checkout and upstream, as printed by `diff --output json`
+#[derive(Serialize)]
+struct DiffOutput {
+    name: String,
+    local: String,
+    upstream: String,
+    patch: String,
+}
+
+fn diff(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    upstream_only: bool,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load(&proj_dirs)?;
+    let repo_path = get_repo_path(proj_dirs, repos);
+    let name = cloned_repo(&repo_path, package_name)?;
+    let package = config.package(&name);
+    let path = repo_path.join(&name);
+
+    let repo = Repository::open(&path)?;
+    let upstream = Upstream::of(&repo, &package);
+    let mut remote = repo.find_remote(&upstream.remote)?;
+
+    let upstream = if upstream_only {
+        let url = remote.url().ok_or("Remote URL is not valid UTF-8")?;
+        remote::fetch_detached(&repo, url, &upstream.branch, auth).map_err(remote::explain)?
+    } else {
+        remote
+            .fetch(&[&upstream.branch], Some(&mut auth.fetch_options()), None)
+            .map_err(remote::explain)?;
+        repo.revparse_single(&upstream.refname())?.id()
+    };
+    let local = repo.head()?.peel_to_commit()?;
+    let upstream = repo.find_commit(upstream)?;
+
+    let diff = repo.diff_tree_to_tree(Some(&local.tree()?), Some(&upstream.tree()?), None)?;
+    let patch = format_patch(&diff, out)?;
+
+    if out.is_json() {
+        out.json(&DiffOutput {
+            name: package_name.to_string(),
+            local: local.id().to_string(),
+            upstream: upstream.id().to_string(),
+            patch,
+        })?;
+    } else if patch.is_empty() {
+        println!("'{}' has no changes to upstream", package_name);
+    } else {
+        print!("{}", patch);
+    }
+
+    Ok(())
+}
+
+/// Formats a diff as a patch, colored like `git diff`
+fn format_patch(diff: &git2::Diff, out: &Formatter) -> Result<String, git2::Error> {
+    let mut patch = String::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        let content = String::from_utf8_lossy(line.content());
+        let color = match line.origin() {
+            '+' => out.fg(out.theme.success),
+            '-' => out.fg(out.theme.warning),
+            'H' => out.fg(out.theme.commit),
+            'F' => out.paint(style::Bold),
+            _ => String::new(),
+        };
+        let origin = match line.origin() {
+            origin @ ('+' | '-' | ' ') => origin.to_string(),
+            _ => String::new(),
+        };
+        patch.push_str(&format!(
+            "{}{}{}{}",
+            color,
+            origin,
+            content.trim_end_matches('\n'),
+            out.paint(style::Reset)
+        ));
+        patch.push('\n');
+        true
+    })?;
+
+    Ok(patch)
+}
+
+fn freeze(proj_dirs: &ProjectDirs, package_names: &[String], freeze: bool) -> Result<(), Error> {
+    let mut frozen = Frozen::load(proj_dirs)?;
+
+    for name in package_names {
+        if freeze {
+            if frozen.freeze(name) {
+                println!("Froze '{}'", name);
+            } else {
+                println!("'{}' is already frozen", name);
+            }
+        } else if frozen.thaw(name) {
+            println!("Thawed '{}'", name);
+        } else {
+            println!("'{}' is not frozen", name);
+        }
+    }
+
+    frozen.save(proj_dirs)
+}
+
+/// Returns the chroot selected with `--chroot`, which falls back to `chroot` from the config if
+/// it is given without a path
+fn chroot_of(
+    chroot: &Option<Option<PathBuf>>,
+    config: &Config,
+    proj_dirs: &ProjectDirs,
+) -> Result<Option<PathBuf>, Error> {
+    match chroot {
+        Some(path) => match path.as_ref().or(config.chroot.as_ref()) {
+            Some(path) => Ok(Some(path.clone())),
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "No chroot given, pass a path to --chroot or set `chroot` in {:?}",
+                    Config::path(proj_dirs)
+                ),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the AUR session from the keyring, `TAUR_AUR_SESSION` or the config, in this order
+fn aur_session(config: &Config) -> Option<String> {
+    keyring::lookup()
+        .or_else(|| std::env::var("TAUR_AUR_SESSION").ok())
+        .filter(|session| !session.is_empty())
+        .or_else(|| config.aur_session.clone())
+}
+
+fn login() -> Result<(), Error> {
+    let session = if termion::is_tty(&std::io::stdin()) {
+        let mut stdout = std::io::stdout();
+        print!("AURSID cookie of a logged-in AUR session: ");
+        stdout.flush()?;
+        let session = std::io::stdin().read_passwd(&mut stdout)?;
+        println!();
+        session.unwrap_or_default()
+    } else {
+        // e.g. piped in from a password manager
+        let mut session = String::new();
+        std::io::stdin().read_line(&mut session)?;
+        session
+    };
+
+    let session = session.trim();
+    if session.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "No session given"));
+    }
+
+    keyring::store(session)?;
+    println!("Stored the AUR session in the keyring");
+
+    Ok(())
+}
+
+async fn vote(
+    session_id: Option<&String>,
+    package_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let session = AurSession::new(session_id)?;
+    let package_base = get_package_base(package_name).await?;
+
+    session.vote(&package_base).await?;
+    println!("Voted for '{}'", package_base);
+
+    Ok(())
+}
+
+async fn flag(
+    session_id: Option<&String>,
+    package_name: &str,
+    comment: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let session = AurSession::new(session_id)?;
+    let package_base = get_package_base(package_name).await?;
+
+    session.flag(&package_base, comment).await?;
+    println!("Flagged '{}' as out-of-date", package_base);
+
+    Ok(())
+}
+
+/// Looks up the package base, which votes and flags are attached to
+async fn get_package_base(package_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let raur = raur::Handle::new();
+    let names = [package_name];
+    let pkgs = error::retry(|| raur.info(&names)).await?;
+    match pkgs.into_iter().next() {
+        Some(pkg) => Ok(pkg.package_base),
+        None => Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("Package '{}' not found", package_name),
+        ))),
+    }
+}
+
+async fn show_comments(
+    proj_dirs: &ProjectDirs,
+    package_name: &str,
+    count: usize,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let raur = raur::Handle::new();
+    let cache_dir = proj_dirs.cache_dir().join("comments");
+
+    let html = comments::fetch_page(raur.client(), &cache_dir, package_name).await?;
+    let mut comments = comments::parse(&html);
+    comments.truncate(count);
+
+    if out.is_json() {
+        out.json(&comments)?;
+        return Ok(());
+    }
+
+    if comments.is_empty() {
+        println!("No comments for '{}'", package_name);
+        return Ok(());
+    }
+
+    for comment in comments {
+        println!(
+            "{}{}{}{} commented on {}{}",
+            out.paint(style::Bold),
+            out.fg(out.theme.package_name),
+            comment.author,
+            out.paint(color::Fg(color::Reset)),
+            comment.date,
+            out.paint(style::Reset)
+        );
+        println!("{}", comment.text);
+        println!();
+    }
+
+    Ok(())
+}
+
+async fn fetch(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    options: FetchOptions,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frozen = Frozen::load(&proj_dirs)?;
+    let packages = Config::load(&proj_dirs)?.packages;
+    let mut last_fetch = LastFetch::load(&proj_dirs)?;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    if !repo_path.exists() {
+        std::fs::create_dir_all(repo_path.as_ref())?;
+    }
+
+    // A status line must be the only thing on stdout, which is what the JSON output ensures
+    let out = &match options.status_format {
+        Some(_) => Formatter::new(OutputFormat::Json, false, out.theme),
+        None => *out,
+    };
+
+    // The table is redrawn in place, so it has to fit on the terminal
+    let fits_terminal = match termion::terminal_size() {
+        Ok((_, rows)) => {
+            find_repos(&repo_path, options.check.recursive_scan)?.len() < rows as usize
+        }
+        Err(_) => false,
+    };
+    let live =
+        !options.quiet && out.is_pretty() && termion::is_tty(&std::io::stdout()) && fits_terminal;
+
+    let check = options.check;
+    let started = Instant::now();
+    let mut results = if live {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let render = async {
+            let mut table = StatusTable::default();
+            while let Some((name, state)) = rx.recv().await {
+                table.update(name, state);
+                table.draw(out);
+            }
+        };
+
+        let checking = check_all_repos(
+            &repo_path,
+            &frozen,
+            &packages,
+            &last_fetch,
+            check,
+            auth,
+            Some(tx),
+        );
+        let (results, _) = tokio::join!(checking, render);
+        println!();
+        results?
+    } else {
+        check_all_repos(
+            &repo_path,
+            &frozen,
+            &packages,
+            &last_fetch,
+            check,
+            auth,
+            None,
+        )
+        .await?
+    };
+
+    for name in &results.fetched {
+        last_fetch.touch(name);
+    }
+    last_fetch.save(&proj_dirs)?;
+
+    if let Some(pattern) = &options.grep {
+        results.update_infos = grep_commits(results.update_infos, pattern);
+    }
+
+    if options.notify && !results.update_infos.is_empty() {
+        let mut names: Vec<&str> = results
+            .update_infos
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect();
+        names.sort_unstable();
+        if let Err(e) = notify::updates(&names) {
+            eprintln!("Warning: Unable to send a notification: {}", e);
+        }
+    }
+
+    let failed = results.failures.len();
+    match options.status_format {
+        Some(StatusFormat::Waybar) => {
+            let mut names: Vec<&str> = results
+                .update_infos
+                .iter()
+                .map(|i| i.name.as_str())
+                .collect();
+            names.sort_unstable();
+            WaybarStatus::new(&names).print()?;
+        }
+        None if options.summary => print_update_summary(out, results.update_infos)?,
+        None => print_update_info(out, results.update_infos)?,
+    }
+    print_recent(out, &results.recent);
+    if options.status_format.is_none() {
+        print_vcs(out, &results.vcs);
+    }
+    if options.ignore_errors {
+        for warning in &results.skipped {
+            eprintln!("Warning: {}", warning);
+        }
+        for (name, _, reason) in &results.failures {
+            eprintln!("Warning: Unable to check '{}': {}", name, reason);
+        }
+    } else {
+        print_check_failures(out, &results.skipped, &results.failures);
+        if !options.quiet {
+            print_failure_summary(out, &results.failures);
+        }
+    }
+    if options.timings && !options.quiet && !out.is_json() {
+        print_timings(out, &results.timings, started.elapsed());
+    }
+
+    if options.exit_code && failed > 0 {
+        return Err(Box::new(Error::other(format!(
+            "{} {} could not be checked",
+            failed,
+            repositories(failed)
+        ))));
+    }
+
+    Ok(())
+}
+
+/// Returns true if the package is left out of fetch, upgrade and `--all`
+///
+/// Frozen packages and packages with `ignore` in their config table are always left out,
+/// a `!` pattern in `.taurignore` doesn't include them again.
+fn is_excluded(name: &str, frozen: &Frozen, ignored: &TaurIgnore, package: &PackageConfig) -> bool {
+    frozen.contains(name) || package.ignore || ignored.is_ignored(name)
+}
+
+/// Fetches all repositories concurrently and collects the ones with upstream changes
+///
+/// If `progress` is given, the state of each repository is reported there while checking.
+async fn check_all_repos(
+    repo_path: &Path,
+    frozen: &Frozen,
+    packages: &BTreeMap<String, PackageConfig>,
+    last_fetch: &LastFetch,
+    options: CheckOptions,
+    auth: &RemoteAuth,
+    progress: Option<Progress>,
+) -> Result<CheckResults, Error> {
+    let dirs = find_repos(repo_path, options.recursive_scan)?;
+    let ignored = TaurIgnore::load(repo_path)?;
+
+    let mut join_handles = vec![];
+
+    for dir in dirs {
+        let name = dir.to_string_lossy();
+        let package = packages.get(name.as_ref()).cloned().unwrap_or_default();
+        if is_excluded(&name, frozen, &ignored, &package) {
+            continue;
+        }
+
+        let age = last_fetch.age(&name);
+        let recent = match (options.stale, age) {
+            (Some(stale), Some(age)) if age < stale => Some(age),
+            _ => None,
+        };
+
+        let path_base = repo_path.to_path_buf();
+        let auth = auth.clone();
+        let progress = progress.clone();
+        let max_commits = options.max_commits;
+        join_handles.push(task::spawn_blocking(move || {
+            let started = Instant::now();
+            let name = dir.to_string_lossy().to_string();
+            progress::report(&progress, &name, RepoState::Fetching);
+
+            let full_path = path_base.join(dir);
+            let fetch = match recent {
+                _ if options.offline => FetchMode::Never,
+                Some(_) => FetchMode::Never,
+                None if options.deep => FetchMode::Always,
+                None => FetchMode::IfMoved,
+            };
+            let verify = options.verify_signatures;
+            let checked =
+                check_repo_updates(full_path, max_commits, fetch, verify, &package, &auth);
+            let outcome = match checked {
+                // Nested repositories are named by their path, not only their directory name
+                Ok(update_info) => CheckOutcome::Checked(update_info.map(|info| UpdateInfo {
+                    name: name.clone(),
+                    ..info
+                })),
+                Err(e) => match e.downcast_ref::<NoUpstream>() {
+                    Some(no_upstream) => CheckOutcome::Skipped(no_upstream.to_string()),
+                    None => match corruption(e.as_ref()) {
+                        Some(message) => CheckOutcome::Failed(
+                            FailureKind::Damaged,
+                            format!(
+                                "{} (the repository looks damaged, clone it again with \
+                                 'taur clone --force {}')",
+                                first_line(message),
+                                name
+                            ),
+                        ),
+                        None => CheckOutcome::Failed(
+                            FailureKind::of(e.as_ref()),
+                            first_line(&e.to_string()),
+                        ),
+                    },
+                },
+            };
+
+            let state = match &outcome {
+                CheckOutcome::Checked(Some(info)) if info.is_ahead_only() => {
+                    RepoState::Ahead(info.ahead)
+                }
+                CheckOutcome::Checked(Some(info)) => {
+                    RepoState::Updates(info.commits.len() + info.omitted)
+                }
+                CheckOutcome::Checked(None) => RepoState::UpToDate,
+                CheckOutcome::Skipped(reason) => RepoState::Skipped(reason.clone()),
+                CheckOutcome::Failed(_, reason) => RepoState::Failed(reason.clone()),
+            };
+            progress::report(&progress, &name, state);
+
+            let vcs = srcinfo::is_vcs_package(&path_base.join(&name), &name);
+            (name, recent, outcome, vcs, started.elapsed())
+        }));
+    }
+
+    let mut results = CheckResults::default();
+
+    for joined in futures::future::join_all(join_handles).await {
+        let (name, recent, outcome, vcs, elapsed) = joined.map_err(Error::other)?;
+        results.timings.push((name.clone(), elapsed));
+        if vcs {
+            results.vcs.push(name.clone());
+        }
+        if let CheckOutcome::Checked(_) = outcome {
+            match recent {
+                Some(age) => results.recent.push((name.clone(), age)),
+                None => results.fetched.push(name.clone()),
+            }
+        }
+        match outcome {
+            CheckOutcome::Checked(Some(info)) if info.is_ahead_only() => {
+                results.skipped.push(format!(
+                    "'{}' is {} {} ahead of upstream (↑{}), pulling it would not fast-forward",
+                    name,
+                    info.ahead,
+                    if info.ahead == 1 { "commit" } else { "commits" },
+                    info.ahead
+                ))
+            }
+            CheckOutcome::Checked(Some(update_info)) => results.update_infos.push(update_info),
+            CheckOutcome::Checked(None) => {}
+            CheckOutcome::Skipped(warning) => results.skipped.push(warning),
+            CheckOutcome::Failed(kind, reason) => results.failures.push((name, kind, reason)),
+        }
+    }
+
+    results.failures.sort_unstable();
+    results.recent.sort_unstable();
+    results.vcs.sort_unstable();
+    results
+        .timings
+        .sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+
+    Ok(results)
+}
+
+/// Returns the message of a git error meaning the repository itself is damaged, e.g. by an
+/// interrupted clone or a full disk
+fn corruption<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a str> {
+    let git_error = git_error(e)?;
+
+    let damaged = match git_error.class() {
+        ErrorClass::Odb
+        | ErrorClass::Zlib
+        | ErrorClass::Object
+        | ErrorClass::Index
+        | ErrorClass::Tree => true,
+        // The directory is there, but not (or no longer) a repository
+        ErrorClass::Repository => git_error.code() == ErrorCode::NotFound,
+        _ => false,
+    };
+
+    damaged.then(|| git_error.message())
+}
+
+/// Returns the git error behind an error, if it is one
+fn git_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a git2::Error> {
+    // Errors of remote operations are wrapped by `remote::explain`
+    e.downcast_ref::<git2::Error>().or_else(|| {
+        e.downcast_ref::<Error>()?
+            .get_ref()?
+            .downcast_ref::<git2::Error>()
+    })
+}
+
+async fn search(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    expression: &str,
+    options: SearchOptions,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.select.is_some() && (out.is_json() || !termion::is_tty(&std::io::stdin())) {
+        return Err(Box::new(Error::new(
+            ErrorKind::Unsupported,
+            "--select needs a terminal to ask on and doesn't work with JSON output",
+        )));
+    }
+
+    let mut pkgs = if options.offline {
+        let cache_dir = proj_dirs.cache_dir();
+        if let Some(age) = packagedb::age(cache_dir).filter(|&age| age > packagedb::TTL) {
+            eprintln!(
+                "Warning: The package database was downloaded {}, update it with 'taur sync-db'",
+                format_age(age.as_secs())
+            );
+        }
+        packagedb::search(packagedb::load(cache_dir)?, expression, options.exact)
+    } else if options.exact {
+        let raur = raur::Handle::new();
+        let names = [expression];
+        error::retry(|| raur.info(&names)).await?
+    } else {
+        let raur = raur::Handle::new();
+        error::retry(|| raur.search_by(expression, options.by.into())).await?
+    };
+    pkgs.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    if options.exact && pkgs.is_empty() {
+        return Err(Box::new(Error::new(
+            ErrorKind::NotFound,
+            format!("Package '{}' not found", expression),
+        )));
+    }
+
+    let marking = options.marking;
+    let cloned = match marking {
+        CloneMarking::None => HashSet::new(),
+        CloneMarking::Mark | CloneMarking::Only => {
+            get_cloned_packages(&get_repo_path(proj_dirs.clone(), repos.clone()))?
+        }
+    };
+    let is_cloned = |name: &str| cloned.contains(&OsString::from(name));
+
+    if marking == CloneMarking::Only {
+        pkgs.retain(|pkg| is_cloned(&pkg.name));
+    }
+
+    let pages = options.limit.map(|limit| pkgs.len().div_ceil(limit).max(1));
+    if let (Some(limit), Some(pages)) = (options.limit, pages) {
+        if options.page > pages {
+            return Err(Box::new(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Page {} is out of range, the {} {} fit on {} {}",
+                    options.page,
+                    pkgs.len(),
+                    if pkgs.len() == 1 { "result" } else { "results" },
+                    pages,
+                    if pages == 1 { "page" } else { "pages" }
+                ),
+            )));
+        }
+        pkgs = pkgs
+            .into_iter()
+            .skip((options.page - 1) * limit)
+            .take(limit)
+            .collect();
+    }
+
+    if out.is_json() {
+        let results: Vec<SearchResult> = pkgs
+            .into_iter()
+            .map(|pkg| SearchResult {
+                cloned: match marking {
+                    CloneMarking::None => None,
+                    CloneMarking::Mark | CloneMarking::Only => Some(is_cloned(&pkg.name)),
+                },
+                name: pkg.name,
+                version: pkg.version,
+                description: pkg.description,
+                popularity: pkg.popularity,
+                votes: pkg.num_votes,
+            })
+            .collect();
+        out.json(&results)?;
+        return Ok(());
+    }
+
+    if pkgs.is_empty() {
+        println!("No packages found");
+        return Ok(());
+    }
+
+    let longest_len = pkgs
+        .iter()
+        .max_by_key(|p| p.name.len())
+        .map(|p| p.name.len())
+        .unwrap_or_default();
+
+    // Numbers to pick the results by
+    let number_width = match options.select {
+        Some(_) => pkgs.len().to_string().len() + 1,
+        None => 0,
+    };
+    let names: Vec<String> = pkgs.iter().map(|pkg| pkg.name.clone()).collect();
+
+    println!(
+        "{}{}Pop  - Name{}Description{}",
+        out.paint(style::Bold),
+        padding(0, number_width),
+        padding(3, longest_len),
+        out.paint(style::Reset)
+    );
+
+    for (i, pkg) in pkgs.into_iter().enumerate() {
+        let number = match options.select {
+            Some(_) => format!("{:>width$} ", i + 1, width = number_width - 1),
+            None => String::new(),
+        };
+        let (marker, marker_width) = if marking != CloneMarking::None && is_cloned(&pkg.name) {
+            (cloned_marker(out), " [cloned]".len())
+        } else {
+            (String::new(), 0)
+        };
+
+        let popularity = format!("{:.2}", pkg.popularity);
+        let description = pkg.description.unwrap_or_default();
+        let description = match options.width {
+            Some(width) => {
+                let used =
+                    number_width + popularity.len() + " - ".len() + longest_len + 1 + marker_width;
+                output::truncate(&description, width.saturating_sub(used))
+            }
+            None => description,
+        };
+
+        println!(
+            "{}{} - {}{}{}{}{}{}",
+            number,
+            popularity,
+            out.fg(out.theme.package_name),
+            pkg.name,
+            out.paint(style::Reset),
+            padding(pkg.name.len(), longest_len + 1),
+            description,
+            marker
+        );
+    }
+
+    if let Some(pages) = pages {
+        println!();
+        print!("Page {} of {}", options.page, pages);
+        if options.page < pages {
+            print!(" (use --page {} for the next page)", options.page + 1);
+        }
+        println!();
+    }
+
+    if let Some(scheme) = options.select {
+        println!();
+        let picked: Vec<String> = select_many(names.len())?
+            .into_iter()
+            .map(|index| names[index].clone())
+            .collect();
+        if picked.is_empty() {
+            println!("Nothing selected");
+            return Ok(());
+        }
+
+        let options = CloneOptions {
+            scheme,
+            ..CloneOptions::default()
+        };
+        clone(proj_dirs, repos, &picked, options, auth).await?;
+    }
+
+    Ok(())
+}
+
+async fn sync_db(proj_dirs: &ProjectDirs, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let cache_dir = proj_dirs.cache_dir();
+
+    if let Some(age) = packagedb::age(cache_dir) {
+        if age < packagedb::TTL && !force {
+            println!(
+                "The package database is up to date (downloaded {}), use --force to download it again",
+                format_age(age.as_secs())
+            );
+            return Ok(());
+        }
+    }
+
+    let raur = raur::Handle::new();
+    let count = packagedb::sync(raur.client(), cache_dir).await?;
+    println!(
+        "Downloaded {} packages to {}",
+        count,
+        packagedb::path(cache_dir).display()
+    );
+
+    Ok(())
+}
+
+async fn provides(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let raur = raur::Handle::new();
+
+    // Map each dependent package to the kinds of dependency it declares
+    let mut dependents: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (strategy, kind) in [
+        (SearchBy::Depends, "depends"),
+        (SearchBy::MakeDepends, "makedepends"),
+    ] {
+        for pkg in error::retry(|| raur.search_by(package_name, strategy)).await? {
+            dependents.entry(pkg.name).or_default().push(kind);
+        }
+    }
+
+    let cloned = get_cloned_packages(&get_repo_path(proj_dirs, repos))?;
+
+    if out.is_json() {
+        let dependents: Vec<Dependent> = dependents
+            .into_iter()
+            .map(|(name, kinds)| Dependent {
+                cloned: cloned.contains(&OsString::from(&name)),
+                name,
+                kinds,
+            })
+            .collect();
+        out.json(&dependents)?;
+        return Ok(());
+    }
+
+    if dependents.is_empty() {
+        println!("No packages depending on '{}' found", package_name);
+        return Ok(());
+    }
+
+    let longest_len = dependents.keys().map(|n| n.len()).max().unwrap_or_default();
+
+    println!(
+        "{}The following packages depend on {}:{}",
+        out.paint(style::Bold),
+        package_name,
+        out.paint(style::Reset)
+    );
+    println!();
+
+    for (name, kinds) in dependents {
+        let marker = if cloned.contains(&OsString::from(&name)) {
+            cloned_marker(out)
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{}{}{}{}{}{}",
+            out.fg(out.theme.package_name),
+            name,
+            out.paint(style::Reset),
+            " ".repeat(longest_len - name.len() + 1),
+            kinds.join(", "),
+            marker
+        );
+    }
+
+    Ok(())
+}
+
+async fn deps(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_name: &str,
+    tree: bool,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let graph = depgraph::resolve(package_name).await?;
+    let cloned = get_cloned_packages(&get_repo_path(proj_dirs, repos))?;
+    let is_cloned = |name: &str| cloned.contains(&OsString::from(name));
+
+    if out.is_json() {
+        let dependencies: Vec<Dependency> = graph
+            .dependencies()
+            .into_iter()
+            .map(|name| Dependency {
+                name,
+                source: graph.source(name),
+                cloned: is_cloned(name),
+                depends: graph.aur.get(name).map(Vec::as_slice).unwrap_or_default(),
+            })
+            .collect();
+        out.json(&dependencies)?;
+        return Ok(());
+    }
+
+    if tree {
+        let mut path = vec![graph.root.as_str()];
+        println!(
+            "{}{}{}",
+            out.fg(out.theme.package_name),
+            graph.root,
+            out.paint(style::Reset)
+        );
+        print_dep_tree(out, &graph, &is_cloned, &mut path, "");
+        return Ok(());
+    }
+
+    let dependencies = graph.dependencies();
+    if dependencies.is_empty() {
+        println!("{} has no dependencies", package_name);
+        return Ok(());
+    }
+
+    for name in dependencies {
+        println!("{}", dep_label(out, &graph, &is_cloned, name));
+    }
+
+    Ok(())
+}
+
+/// Prints the dependencies of the last package in `path` like pactree does
+fn print_dep_tree<'a>(
+    out: &Formatter,
+    graph: &'a DepTree,
+    is_cloned: &dyn Fn(&str) -> bool,
+    path: &mut Vec<&'a str>,
+    indent: &str,
+) {
+    let deps = match path.last().and_then(|name| graph.aur.get(*name)) {
+        Some(deps) => deps,
+        None => return,
+    };
+
+    for (i, dep) in deps.iter().enumerate() {
+        let last = i + 1 == deps.len();
+        let branch = if last { "└─" } else { "├─" };
+
+        if path.contains(&dep.as_str()) {
+            println!(
+                "{}{}{} {}(cycle){}",
+                indent,
+                branch,
+                dep,
+                out.fg(out.theme.warning),
+                out.paint(style::Reset)
+            );
+            continue;
+        }
+
+        println!(
+            "{}{}{}",
+            indent,
+            branch,
+            dep_label(out, graph, is_cloned, dep)
+        );
+
+        path.push(dep);
+        let child_indent = format!("{}{}", indent, if last { "  " } else { "│ " });
+        print_dep_tree(out, graph, is_cloned, path, &child_indent);
+        path.pop();
+    }
+}
+
+fn dep_label(
+    out: &Formatter,
+    graph: &DepTree,
+    is_cloned: &dyn Fn(&str) -> bool,
+    name: &str,
+) -> String {
+    match graph.source(name) {
+        Source::Aur if is_cloned(name) => format!(
+            "{}{}{}{}",
+            out.fg(out.theme.package_name),
+            name,
+            out.paint(style::Reset),
+            cloned_marker(out)
+        ),
+        Source::Aur => format!(
+            "{}{}{} [aur]",
+            out.fg(out.theme.package_name),
+            name,
+            out.paint(style::Reset)
+        ),
+        Source::Repo => format!("{} [repo]", name),
+        Source::Missing => format!(
+            "{} {}{}[missing]{}",
+            name,
+            out.paint(style::Bold),
+            out.fg(out.theme.warning),
+            out.paint(style::Reset)
+        ),
+    }
+}
+
+async fn upgrade(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    options: UpgradeOptions,
+    hook: Option<&String>,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // A dry run changes nothing, so it compares with the last fetch instead of fetching
+    if !options.dry_run {
+        options.build.check()?;
+    }
+
+    let frozen = Frozen::load(&proj_dirs)?;
+    let mut packages = Config::load(&proj_dirs)?.packages;
+    let mut last_fetch = LastFetch::load(&proj_dirs)?;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    if !repo_path.exists() && !options.dry_run {
+        std::fs::create_dir_all(repo_path.as_ref())?;
+    }
+
+    let check = CheckOptions {
+        offline: options.dry_run,
+        recursive_scan: options.recursive_scan,
+        ..CheckOptions::default()
+    };
+    let results = check_all_repos(
+        &repo_path,
+        &frozen,
+        &packages,
+        &last_fetch,
+        check,
+        auth,
+        None,
+    )
+    .await?;
+    if !options.dry_run {
+        for name in &results.fetched {
+            last_fetch.touch(name);
+        }
+        last_fetch.save(&proj_dirs)?;
+    }
+
+    let package_names: Vec<String> = results
+        .update_infos
+        .iter()
+        .map(|i| i.name.clone())
+        .collect();
+
+    print_update_info(out, results.update_infos)?;
+    print_check_failures(out, &results.skipped, &results.failures);
+
+    // All pending updates are shown above, but only the selected ones are upgraded
+    let package_names = match options.selected.is_empty() {
+        true => package_names,
+        false => {
+            for name in &options.selected {
+                if !package_names.contains(name) {
+                    eprintln!("Warning: '{}' has no upstream changes, skipping it", name);
+                }
+            }
+            package_names
+                .into_iter()
+                .filter(|name| options.selected.contains(name))
+                .collect()
+        }
+    };
+
+    if options.dry_run {
+        if !package_names.is_empty() {
+            let order = get_build_order(&repo_path, &package_names)?;
+            println!(
+                "{}The following packages would be upgraded (in build order):{}",
+                out.paint(style::Bold),
+                out.paint(style::Reset)
+            );
+            for name in order {
+                println!("  {}", name);
+            }
+            println!();
+        }
+        println!("(compared with the last fetch, run 'taur fetch' to see newer upstream changes)");
+        return Ok(());
+    }
+    if package_names.is_empty() {
+        return Ok(());
+    }
+
+    let policy = FailurePolicy::new(options.keep_going);
+    if !confirm("Proceed with upgrade?", options.build.no_confirm)? {
+        println!("Upgrade aborted");
+        return Ok(());
+    }
+
+    let packages: Vec<(String, PackageConfig)> = package_names
+        .into_iter()
+        .map(|name| {
+            let package = packages.remove(&name).unwrap_or_default();
+            (name, package)
+        })
+        .collect();
+
+    let out = *out;
+    let repo_path = repo_path.to_path_buf();
+    let hook = hook.cloned();
+    let auth = auth.clone();
+    task::spawn_blocking(move || {
+        upgrade_packages(&repo_path, &packages, &options, &policy, hook, &auth, &out)
+    })
+    .await??;
+
+    Ok(())
+}
+
+/// Pulls, builds and installs the given packages, each with its own settings
+fn upgrade_packages(
+    repo_path: &Path,
+    packages: &[(String, PackageConfig)],
+    options: &UpgradeOptions,
+    policy: &FailurePolicy,
+    hook: Option<String>,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Error> {
+    let build_options = &options.build;
+    let review = options.review.then_some(Review {
+        out: *out,
+        no_confirm: build_options.no_confirm,
+    });
+    let mut failed: Vec<(String, &str, String)> = Vec::new();
+
+    // Pull everything first, so the build order is computed from the updated .SRCINFO files
+    let mut pulled: Vec<String> = Vec::new();
+    let mut declined = 0;
+    for (name, package) in packages.iter().take_while(|_| policy.proceed()) {
+        print_phase(out, name, "pulling");
+        match pull_package(repo_path, name, false, package, review, auth) {
+            Ok(PullStatus::Pulled {
+                head,
+                commits,
+                audit,
+            }) => {
+                for commit in &commits {
+                    print_commit(out, commit);
+                }
+                // The review already pointed out the findings
+                if review.is_none() {
+                    print!("{}", format_findings(out, &audit));
+                }
+                if let Some(hook) = &hook {
+                    run_post_pull_hook(hook, repo_path, name, &head);
+                }
+                pulled.push(name.clone());
+            }
+            Ok(PullStatus::Declined) => {
+                println!("Not upgrading '{}'", name);
+                declined += 1;
+            }
+            Ok(_) => {
+                println!("No new commits to pull");
+                pulled.push(name.clone());
+            }
+            Err(e) => {
+                policy.record_failure();
+                failed.push((name.clone(), "pull", e.to_string()));
+            }
+        }
+    }
+
+    let mut upgraded: Vec<String> = Vec::new();
+    let mut built: Vec<PathBuf> = Vec::new();
+    // Without a build order nothing can be built, but the pulls above are still reported
+    let build_order = match get_build_order(repo_path, &pulled) {
+        Ok(order) => order,
+        Err(e) => {
+            policy.record_failure();
+            let reason = e.to_string();
+            failed.extend(pulled.drain(..).map(|name| (name, "order", reason.clone())));
+            Vec::new()
+        }
+    };
+    for name in build_order.into_iter().take_while(|_| policy.proceed()) {
+        let package_dir = repo_path.join(&name);
+
+        let existing = if build_options.rebuild {
+            None
+        } else {
+            build::already_built(&package_dir, build_options.output_dir.as_deref())
+        };
+        let files = match existing {
+            Some(files) => {
+                print_phase(out, &name, "already built");
+                files
+            }
+            None => {
+                print_phase(out, &name, "building");
+                let extra_args = build_options.makepkg_args(&name);
+                match build::build_package(&package_dir, build_options, &built, extra_args) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        policy.record_failure();
+                        failed.push((name, "build", e.to_string()));
+                        continue;
+                    }
+                }
+            }
+        };
+
+        print_phase(out, &name, "installing");
+        built.extend(files.iter().cloned());
+        match build::install_packages(&files, build_options) {
+            Ok(_) => upgraded.push(name),
+            Err(e) => {
+                policy.record_failure();
+                failed.push((name, "install", e.to_string()));
+            }
+        }
+    }
+
+    println!();
+    if !upgraded.is_empty() {
+        println!(
+            "{}Upgraded:{} {}",
+            out.paint(style::Bold),
+            out.paint(style::Reset),
+            upgraded.join(", ")
+        );
+    }
+    if !failed.is_empty() {
+        println!(
+            "{}{}Failed:{}",
+            out.paint(style::Bold),
+            out.fg(out.theme.warning),
+            out.paint(style::Reset)
+        );
+        for (name, phase, reason) in &failed {
+            println!("  {} ({}): {}", name, phase, reason);
+        }
+
+        let skipped = packages.len() - upgraded.len() - failed.len() - declined;
+        return Err(policy.error(
+            format!(
+                "{} {} could not be upgraded",
+                failed.len(),
+                if failed.len() == 1 {
+                    "package"
+                } else {
+                    "packages"
+                }
+            ),
+            skipped,
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct OutdatedPackage {
+    name: String,
+    installed: String,
+    aur: String,
+}
+
+/// Lists the foreign packages with a newer version in the AUR than installed
+async fn check(pacman: &Path, out: &Formatter) -> Result<(), Box<dyn std::error::Error>> {
+    build::check_executable(pacman, "pacman")?;
+    let installed = build::foreign_versions(pacman)?;
+    let names: Vec<String> = installed.keys().cloned().collect();
+
+    let raur = raur::Handle::new();
+    let info = rpc::info(&raur, &names).await?;
+
+    let mut outdated: Vec<OutdatedPackage> = info
+        .found
+        .into_iter()
+        .filter_map(|pkg| {
+            let version = installed.get(&pkg.name)?;
+            (version::vercmp(version, &pkg.version) == std::cmp::Ordering::Less).then(|| {
+                OutdatedPackage {
+                    installed: version.clone(),
+                    name: pkg.name,
+                    aur: pkg.version,
+                }
+            })
+        })
+        .collect();
+    outdated.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    if out.is_json() {
+        out.json(&outdated)?;
+    } else if outdated.is_empty() {
+        println!("All {} foreign packages are up to date", installed.len());
+    } else {
+        for pkg in &outdated {
+            println!(
+                "{}{}{} {} -> {}{}{}",
+                out.fg(out.theme.package_name),
+                pkg.name,
+                out.paint(style::Reset),
+                pkg.installed,
+                out.fg(out.theme.success),
+                pkg.aur,
+                out.paint(style::Reset)
+            );
+        }
+    }
+
+    // Locally built packages or ones removed from the AUR can't be checked
+    if !info.missing.is_empty() {
+        eprintln!("Not in the AUR: {}", info.missing.join(", "));
+    }
+
+    Ok(())
+}
+
+/// How `adopt` clones the repositories of installed packages
+#[derive(Clone, Copy)]
+struct AdoptOptions {
+    /// Clone after confirming, without this only the packages are listed
+    clone: bool,
+    no_confirm: bool,
+    scheme: CloneScheme,
+    keep_going: bool,
+}
+
+/// Clones the repositories of all installed foreign packages that are in the AUR and not
+/// cloned yet, after confirming
+async fn adopt(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    options: AdoptOptions,
+    pacman: &Path,
+    auth: &RemoteAuth,
+) -> Result<(), Box<dyn std::error::Error>> {
+    build::check_executable(pacman, "pacman")?;
+    let foreign: Vec<String> = build::foreign_versions(pacman)?.into_keys().collect();
+    if foreign.is_empty() {
+        println!("No foreign packages installed");
+        return Ok(());
+    }
+
+    let raur = raur::Handle::new();
+    let info = rpc::info(&raur, &foreign).await?;
+
+    let repo_path = get_repo_path(proj_dirs, repos);
+    // Split packages live in the repository of their package base
+    let mut bases: Vec<String> = info
+        .found
+        .iter()
+        .map(|pkg| pkg.package_base.clone())
+        .filter(|base| find_package_repo(&repo_path, base).is_none())
+        .collect();
+    bases.sort_unstable();
+    bases.dedup();
+
+    // Locally built packages or ones removed from the AUR have nothing to clone
+    if !info.missing.is_empty() {
+        eprintln!("Not in the AUR: {}", info.missing.join(", "));
+    }
+
+    if bases.is_empty() {
+        println!(
+            "All {} foreign packages found in the AUR are cloned",
+            info.found.len()
+        );
+        return Ok(());
+    }
+
+    println!("Not cloned yet: {}", bases.join(", "));
+    if !options.clone {
+        return Ok(());
+    }
+
+    let question = format!("Clone {} {}?", bases.len(), repositories(bases.len()));
+    if !confirm(&question, options.no_confirm)? {
+        println!("Nothing cloned");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(repo_path.as_ref())?;
+    let policy = FailurePolicy::new(options.keep_going);
+    let mut cloned = 0;
+    let mut failed = 0;
+    for base in bases.iter().take_while(|_| policy.proceed()) {
+        match clone_repo(&repo_path, base, false, None, options.scheme, auth) {
+            Ok(()) => cloned += 1,
+            Err(e) => {
+                policy.record_failure();
+                eprintln!("Error while cloning '{}': {}", base, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        let message = format!(
+            "{} of {} {} could not be cloned",
+            failed,
+            bases.len(),
+            repositories(bases.len())
+        );
+        return Err(Box::new(
+            policy.error(message, bases.len() - cloned - failed),
+        ));
+    }
+
+    Ok(())
+}
+
+fn order(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    all: bool,
+    out: &Formatter,
+) -> Result<(), Error> {
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    let package_names = select_repos(&proj_dirs, &repo_path, package_names, all, false, "order")?;
+    let order = get_build_order(&repo_path, &package_names)?;
+
+    if out.is_json() {
+        out.json(&order)?;
+        return Ok(());
+    }
+
+    for name in order {
+        println!("{}", name);
+    }
+
+    Ok(())
+}
+
+/// Builds the given packages one after another, reusing package files that were already built
+///
+/// Failures are reported at the end, together with the package files that were built.
+async fn build(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: BuildOptions,
+    keep_going: bool,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    options.check()?;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    let package_names = select_repos(&proj_dirs, &repo_path, package_names, false, false, "build")?;
+    let total = package_names.len();
+
+    let out = *out;
+    let policy = FailurePolicy::new(keep_going);
+    let (files, done, failed, policy) = task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        let mut done = 0;
+        let mut failed: Vec<(String, String)> = Vec::new();
+        for name in package_names.into_iter().take_while(|_| policy.proceed()) {
+            let package_dir = repo_path.join(&name);
+            match build::already_built(&package_dir, options.output_dir.as_deref()) {
+                Some(built) => {
+                    print_phase(
+                        &out,
+                        &name,
+                        "already built (use 'taur rebuild' to build again)",
+                    );
+                    files.extend(built);
+                    done += 1;
+                }
+                None => {
+                    print_phase(&out, &name, "building");
+                    let extra_args = options.makepkg_args(&name);
+                    match build::build_package(&package_dir, &options, &files, extra_args) {
+                        Ok(built) => {
+                            files.extend(built);
+                            done += 1;
+                        }
+                        Err(e) => {
+                            policy.record_failure();
+                            failed.push((name, e.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+        (files, done, failed, policy)
+    })
+    .await?;
+
+    println!();
+    if !files.is_empty() {
+        println!(
+            "{}Package files:{}",
+            out.paint(style::Bold),
+            out.paint(style::Reset)
+        );
+        for file in &files {
+            println!("  {}", file.display());
+        }
+    }
+    if !failed.is_empty() {
+        println!(
+            "{}{}Failed:{}",
+            out.paint(style::Bold),
+            out.fg(out.theme.warning),
+            out.paint(style::Reset)
+        );
+        for (name, reason) in &failed {
+            println!("  {}: {}", name, reason);
+        }
+        return Err(Box::new(policy.error(
+            format!(
+                "{} {} could not be built",
+                failed.len(),
+                if failed.len() == 1 {
+                    "package"
+                } else {
+                    "packages"
+                }
+            ),
+            total - done - failed.len(),
+        )));
+    }
+
+    Ok(())
+}
+
+/// Clones the given packages that aren't cloned yet and pulls the others, then builds them in
+/// dependency order and installs them all at once
+async fn install(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: InstallOptions,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    options.build.check()?;
+    let mut packages = Config::load(&proj_dirs)?.packages;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos.clone());
+
+    let mut cloned: Vec<String> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    for name in package_names {
+        match find_package_repo(&repo_path, name) {
+            Some(repo) if !cloned.contains(&repo) => cloned.push(repo),
+            Some(_) => {}
+            None => missing.push(name.clone()),
+        }
+    }
+    let policy = FailurePolicy::new(options.keep_going);
+    let mut failed: Vec<(String, &str, String)> = Vec::new();
+    if !missing.is_empty() {
+        let clone_options = CloneOptions {
+            keep_going: options.keep_going,
+            scheme: options.scheme,
+            ..CloneOptions::default()
+        };
+        if let Err(e) = clone(proj_dirs, repos, &missing, clone_options, auth).await {
+            if !options.keep_going {
+                return Err(e);
+            }
+            policy.record_failure();
+            // Whatever was cloned is still installed
+            for name in missing
+                .iter()
+                .filter(|name| find_package_repo(&repo_path, name).is_none())
+            {
+                failed.push((name.clone(), "clone", e.to_string()));
+            }
+        }
+    }
+
+    // Split packages were cloned as their package base
+    let mut names = cloned.clone();
+    for repo in missing
+        .iter()
+        .filter_map(|name| find_package_repo(&repo_path, name))
+    {
+        if !names.contains(&repo) {
+            names.push(repo);
+        }
+    }
+    // Packages that could not be cloned are already among the failures
+    let total = names.len() + failed.len();
+
+    let out = *out;
+    let auth = auth.clone();
+    let review = options.review.then_some(Review {
+        out,
+        no_confirm: options.build.no_confirm,
+    });
+    let options = options.build;
+    task::spawn_blocking(move || -> Result<(), Error> {
+        for name in cloned.iter().take_while(|_| policy.proceed()) {
+            print_phase(&out, name, "pulling");
+            let package = packages.remove(name).unwrap_or_default();
+            match pull_package(&repo_path, name, false, &package, review, &auth) {
+                Ok(PullStatus::Pulled { commits, .. }) => {
+                    for commit in &commits {
+                        print_commit(&out, commit);
+                    }
+                }
+                Ok(PullStatus::Declined) => {
+                    println!("Install aborted");
+                    return Ok(());
+                }
+                Ok(_) => println!("No new commits to pull"),
+                Err(e) => {
+                    policy.record_failure();
+                    failed.push((name.clone(), "pull", e.to_string()));
+                }
+            }
+        }
+        names.retain(|name| !failed.iter().any(|(failed, _, _)| failed == name));
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        let mut built: Vec<String> = Vec::new();
+        let build_order = get_build_order(&repo_path, &names)?;
+        for name in build_order.into_iter().take_while(|_| policy.proceed()) {
+            let package_dir = repo_path.join(&name);
+            match build::already_built(&package_dir, options.output_dir.as_deref()) {
+                Some(existing) => {
+                    print_phase(&out, &name, "already built");
+                    files.extend(existing);
+                    built.push(name);
+                }
+                None => {
+                    print_phase(&out, &name, "building");
+                    let extra_args = options.makepkg_args(&name);
+                    match build::build_package(&package_dir, &options, &files, extra_args) {
+                        Ok(new) => {
+                            files.extend(new);
+                            built.push(name);
+                        }
+                        Err(e) => {
+                            policy.record_failure();
+                            failed.push((name, "build", e.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Without --keep-going, nothing is installed after a failure
+        if policy.proceed() && !files.is_empty() {
+            print_phase(&out, &built.join(", "), "installing");
+            build::install_packages(&files, &options)?;
+        }
+
+        if !failed.is_empty() {
+            println!();
+            println!(
+                "{}{}Failed:{}",
+                out.paint(style::Bold),
+                out.fg(out.theme.warning),
+                out.paint(style::Reset)
+            );
+            for (name, phase, reason) in &failed {
+                println!("  {} ({}): {}", name, phase, reason);
+            }
+            let skipped = total - built.len() - failed.len();
+            return Err(policy.error(
+                format!(
+                    "{} {} could not be installed",
+                    failed.len(),
+                    if failed.len() == 1 {
+                        "package"
+                    } else {
+                        "packages"
+                    }
+                ),
+                skipped,
+            ));
+        }
+
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+async fn rebuild(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: RebuildOptions,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    options.build.check()?;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    let package_names = select_repos(
+        &proj_dirs,
+        &repo_path,
+        package_names,
+        options.all,
+        false,
+        "rebuild",
+    )?;
+
+    // Concurrent makepkg runs would fight over the pacman lock when installing dependencies
+    let mut build_options = options.build;
+    let jobs = options.jobs.max(1);
+    if jobs > 1 {
+        build_options.no_deps = true;
+    }
+
+    let total = package_names.len();
+    let policy = FailurePolicy::new(options.keep_going);
+    let builds = package_names
+        .into_iter()
+        .take_while(|_| policy.proceed())
+        .map(|name| {
+            let package_dir = repo_path.join(&name);
+            let build_options = build_options.clone();
+            let out = *out;
+            task::spawn_blocking(move || {
+                print_phase(&out, &name, "rebuilding");
+                let extra_args = build_options.makepkg_args(&name);
+                let result = build::build_package(&package_dir, &build_options, &[], extra_args);
+                (name, result)
+            })
+        });
+
+    let mut rebuilt: Vec<String> = Vec::new();
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    let mut results = futures::stream::iter(builds).buffer_unordered(jobs);
+    while let Some(joined) = results.next().await {
+        let (name, result) = joined.map_err(Error::other)?;
+        match result {
+            Ok(built) => {
+                rebuilt.push(name);
+                files.extend(built);
+            }
+            Err(e) => {
+                policy.record_failure();
+                failed.push((name, e.to_string()));
+            }
+        }
+    }
+    drop(results);
+    rebuilt.sort_unstable();
+    failed.sort_unstable();
+
+    if options.install && !files.is_empty() {
+        task::spawn_blocking(move || build::install_packages(&files, &build_options)).await??;
+    }
+
+    println!();
+    if !rebuilt.is_empty() {
+        println!(
+            "{}Rebuilt:{} {}",
+            out.paint(style::Bold),
+            out.paint(style::Reset),
+            rebuilt.join(", ")
+        );
+    }
+    if !failed.is_empty() {
+        println!(
+            "{}{}Failed:{}",
+            out.paint(style::Bold),
+            out.fg(out.theme.warning),
+            out.paint(style::Reset)
+        );
+        for (name, reason) in &failed {
+            println!("  {}: {}", name, reason);
+        }
+        let skipped = total - rebuilt.len() - failed.len();
+        return Err(Box::new(policy.error(
+            format!(
+                "{} {} could not be rebuilt",
+                failed.len(),
+                if failed.len() == 1 {
+                    "package"
+                } else {
+                    "packages"
+                }
+            ),
+            skipped,
+        )));
+    }
+
+    Ok(())
+}
+
+async fn list(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    options: ListOptions,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut last_fetch = LastFetch::load(&proj_dirs)?;
+    let packages = Config::load(&proj_dirs)?.packages;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+
+    // Without --fetch, everything comes from the local refs, so this works offline
+    if options.fetch {
+        let frozen = Frozen::load(&proj_dirs)?;
+        let check = CheckOptions::default();
+        let results = check_all_repos(
+            &repo_path,
+            &frozen,
+            &packages,
+            &last_fetch,
+            check,
+            auth,
+            None,
+        )
+        .await?;
+        for name in &results.fetched {
+            last_fetch.touch(name);
+        }
+        last_fetch.save(&proj_dirs)?;
+
+        // stdout only has the list, which may be read by scripts
+        for (name, _, reason) in &results.failures {
+            eprintln!("Warning: Unable to fetch '{}': {}", name, reason);
+        }
+    }
+
+    let mut entries: Vec<RepoEntry> = Vec::new();
+    for dir in get_cloned_packages(&repo_path)? {
+        let name = dir.to_string_lossy().to_string();
+        let package = packages.get(&name).cloned().unwrap_or_default();
+        match read_repo_entry(&repo_path.join(&dir), &name, &package, &last_fetch) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("Warning: Unable to read repository '{}': {}", name, e),
+        }
+    }
+
+    // Sort by name first, so ties stay in alphabetical order
+    entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    match options.sort_by {
+        SortBy::Name => {}
+        SortBy::Updated => entries.sort_by_key(|e| Reverse(e.updated)),
+        SortBy::Behind => entries.sort_by_key(|e| Reverse(e.behind)),
+    }
+
+    if options.porcelain {
+        for entry in entries {
+            println!(
+                "{} {} {} {} {}",
+                entry.name,
+                entry.behind,
+                entry.ahead,
+                u8::from(entry.dirty),
+                entry.head_id
+            );
+        }
+        return Ok(());
+    }
+
+    if out.is_json() {
+        out.json(&entries)?;
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No repositories cloned yet");
+        return Ok(());
+    }
+
+    let width = entries
+        .iter()
+        .map(|e| e.name.len())
+        .max()
+        .unwrap_or_default()
+        .max(4);
+    let version_width = entries
+        .iter()
+        .map(|e| e.version.as_deref().map_or(1, str::len))
+        .max()
+        .unwrap_or_default()
+        .max(7);
+    let pulled: Vec<String> = entries
+        .iter()
+        .map(|e| {
+            e.last_pulled
+                .map_or_else(|| String::from("never"), format_timestamp)
+        })
+        .collect();
+    let pulled_width = pulled
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or_default()
+        .max(11);
+
+    println!(
+        "{}Name{} Version{} Head    Behind  Dirty  Last pulled{} Last checked{}",
+        out.paint(style::Bold),
+        padding(4, width),
+        padding(7, version_width),
+        padding(11, pulled_width),
+        out.paint(style::Reset)
+    );
+
+    for (entry, pulled) in entries.into_iter().zip(pulled) {
+        let version = entry.version.unwrap_or_else(|| String::from("-"));
+        let behind = if entry.behind > 0 {
+            format!(
+                "{}{:<7}{}",
+                out.fg(out.theme.header),
+                entry.behind,
+                out.paint(style::Reset)
+            )
+        } else {
+            format!("{:<7}", 0)
+        };
+        let dirty = if entry.dirty {
+            format!(
+                "{}yes{}   ",
+                out.fg(out.theme.warning),
+                out.paint(style::Reset)
+            )
+        } else {
+            String::from("no    ")
+        };
+        let checked = match entry.last_checked {
+            Some(age) => format_age(age),
+            None => String::from("never"),
+        };
+        let vcs = if entry.vcs {
+            " (VCS, upstream tracked on build)"
+        } else {
+            ""
+        };
+
+        println!(
+            "{}{}{}{} {}{} {} {} {} {}{} {}{}",
+            out.fg(out.theme.package_name),
+            entry.name,
+            out.paint(style::Reset),
+            padding(entry.name.len(), width),
+            version,
+            padding(version.len(), version_width),
+            entry.head,
+            behind,
+            dirty,
+            pulled,
+            padding(pulled.len(), pulled_width),
+            checked,
+            vcs
+        );
+    }
+
+    if !options.fetch {
+        println!();
+        println!("Behind as of the last fetch, use --fetch to fetch first");
+    }
+
+    Ok(())
+}
+
+/// Shows the repositories in a full-screen view and pulls, builds or opens the selected one
+///
+/// The actions run on the regular screen, so their output and prompts look as usual.
+async fn tui(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    build_options: BuildOptions,
+    hook: Option<&String>,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !termion::is_tty(&std::io::stdin()) || !termion::is_tty(&std::io::stdout()) {
+        return Err(Box::new(Error::new(
+            ErrorKind::Unsupported,
+            "The interactive view needs a terminal",
+        )));
+    }
+
+    let repo_path = get_repo_path(proj_dirs.clone(), repos.clone());
+    let mut view = tui::View::new(tui_rows(&proj_dirs, &repo_path, auth)?);
+
+    loop {
+        let (verb, result) = match view.next_action(out)? {
+            tui::Action::Quit => return Ok(()),
+            tui::Action::Fetch => {
+                let options = FetchOptions::default();
+                let result = fetch(proj_dirs.clone(), repos.clone(), options, auth, out).await;
+                ("fetching", result)
+            }
+            tui::Action::Pull(name) => {
+                let options = PullOptions {
+                    review: true,
+                    no_confirm: build_options.no_confirm,
+                    ..PullOptions::default()
+                };
+                let names = [name];
+                let result = pull(
+                    proj_dirs.clone(),
+                    repos.clone(),
+                    &names,
+                    options,
+                    hook,
+                    auth,
+                    out,
+                )
+                .await;
+                ("pulling", result)
+            }
+            tui::Action::Build(name) => {
+                let options = build_options.clone();
+                let names = [name];
+                let result = build(
+                    proj_dirs.clone(),
+                    repos.clone(),
+                    &names,
+                    options,
+                    false,
+                    out,
+                )
+                .await;
+                ("building", result)
+            }
+            tui::Action::Shell(name) => {
+                let shell = std::env::var_os("SHELL").unwrap_or_else(|| OsString::from("sh"));
+                println!("Opening a shell in '{}', exit it to return", name);
+                let status = std::process::Command::new(shell)
+                    .current_dir(repo_path.join(&name))
+                    .status();
+                if let Err(e) = status {
+                    view.set_status(format!("Error while opening a shell: {}", e));
+                }
+                view.set_rows(tui_rows(&proj_dirs, &repo_path, auth)?);
+                continue;
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error while {}: {}", verb, e);
+        }
+        print!("Press Enter to return to the list ");
+        std::io::stdout().flush()?;
+        std::io::stdin().read_line(&mut String::new())?;
+
+        view.set_rows(tui_rows(&proj_dirs, &repo_path, auth)?);
+    }
+}
+
+/// Writes the systemd user units running `taur fetch` every `interval`
+///
+/// The fetch uses the same profile and workspace as this command.
+fn install_timer(args: &Args, interval: &str, notify: bool) -> Result<(), Error> {
+    timer::check_interval(interval)?;
+
+    let mut fetch_args = Vec::new();
+    if let Some(profile) = &args.profile {
+        fetch_args.extend([String::from("--profile"), profile.clone()]);
+    }
+    if let Some(workspace) = &args.workspace {
+        fetch_args.extend([String::from("--workspace"), workspace.clone()]);
+    }
+    fetch_args.extend([String::from("fetch"), String::from("--quiet")]);
+    if notify {
+        fetch_args.push(String::from("--notify"));
+    }
+
+    let dir = timer::unit_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let taur = std::env::current_exe()?;
+    std::fs::write(dir.join(timer::SERVICE), timer::service(&taur, &fetch_args))?;
+    std::fs::write(dir.join(timer::TIMER), timer::timer(interval))?;
+
+    println!(
+        "Wrote {} and {} to {}",
+        timer::SERVICE,
+        timer::TIMER,
+        dir.display()
+    );
+    println!("Start the timer with:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now {}", timer::TIMER);
+
+    Ok(())
+}
+
+/// Stops the timer and removes the systemd user units written by `install_timer`
+fn remove_timer() -> Result<(), Error> {
+    let dir = timer::unit_dir()?;
+
+    // systemd can't disable a timer whose unit file is gone, so this comes first
+    if dir.join(timer::TIMER).exists() {
+        let status = std::process::Command::new("systemctl")
+            .args(["--user", "disable", "--now", timer::TIMER])
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: Unable to stop {}: {}", timer::TIMER, status),
+            Err(e) => eprintln!("Warning: Unable to run systemctl: {}", e),
+        }
+    }
+
+    let mut removed = false;
+    for unit in [timer::TIMER, timer::SERVICE] {
+        match std::fs::remove_file(dir.join(unit)) {
+            Ok(()) => {
+                println!("Removed {}", dir.join(unit).display());
+                removed = true;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if !removed {
+        println!("No timer installed in {}", dir.display());
+    }
+
+    Ok(())
+}
+
+/// Reads the state of the cloned repositories for the interactive view, without fetching
+fn tui_rows(
+    proj_dirs: &ProjectDirs,
+    repo_path: &Path,
+    auth: &RemoteAuth,
+) -> Result<Vec<tui::Row>, Box<dyn std::error::Error>> {
+    let last_fetch = LastFetch::load(proj_dirs)?;
+    let packages = Config::load(proj_dirs)?.packages;
+
+    let mut rows = Vec::new();
+    for dir in get_cloned_packages(repo_path)? {
+        let name = dir.to_string_lossy().to_string();
+        let package = packages.get(&name).cloned().unwrap_or_default();
+        let path = repo_path.join(&dir);
+        let entry = match read_repo_entry(&path, &name, &package, &last_fetch) {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Warning: Unable to read repository '{}': {}", name, e);
+                continue;
+            }
+        };
+        let commits = match check_repo_updates(path, None, FetchMode::Never, false, &package, auth)
+        {
+            Ok(Some(info)) => info.commits.iter().map(|c| first_line(c)).collect(),
+            _ => Vec::new(),
+        };
+
+        rows.push(tui::Row {
+            name,
+            head: entry.head,
+            behind: entry.behind,
+            ahead: entry.ahead,
+            dirty: entry.dirty,
+            commits,
+        });
+    }
+    rows.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(rows)
+}
+
+fn read_repo_entry(
+    path: &Path,
+    name: &str,
+    package: &PackageConfig,
+    last_fetch: &LastFetch,
+) -> Result<RepoEntry, git2::Error> {
+    let repo = Repository::open(path)?;
+    let head = repo.head()?.peel_to_commit()?;
+
+    // Without an upstream, there is nothing to be behind or ahead of
+    let upstream = match Upstream::is_overridden(package) {
+        true => Upstream::of(&repo, package).refname(),
+        false => String::from("@{u}"),
+    };
+    let (ahead, behind) = match repo.revparse_single(&upstream) {
+        Ok(upstream) => repo.graph_ahead_behind(head.id(), upstream.id())?,
+        Err(_) => (0, 0),
+    };
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let dirty = !repo.statuses(Some(&mut options))?.is_empty();
+
+    // Clones and pulls are logged in the reflog of the branch, newest entry first
+    let last_pulled = repo
+        .head()?
+        .name()
+        .and_then(|branch| repo.reflog(branch).ok())
+        .and_then(|reflog| {
+            reflog
+                .get(0)
+                .map(|entry| entry.committer().when().seconds())
+        });
+
+    Ok(RepoEntry {
+        name: name.to_string(),
+        version: Srcinfo::read(path)
+            .ok()
+            .and_then(|srcinfo| srcinfo.version()),
+        head: head.id().to_string()[..7].to_string(),
+        updated: head.time().seconds(),
+        behind,
+        ahead,
+        dirty,
+        last_pulled,
+        last_checked: last_fetch.age(name),
+        vcs: srcinfo::is_vcs_package(path, name),
+        head_id: head.id().to_string(),
+    })
+}
+
+fn orphans(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    remove: bool,
+    no_confirm: bool,
+    pacman: &Path,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    build::check_executable(pacman, "pacman")?;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    let installed = build::installed_packages(false, pacman)?;
+    let foreign = build::installed_packages(true, pacman)?;
+
+    let mut orphans: Vec<Orphan> = Vec::new();
+    for dir in get_dir_list(&repo_path)? {
+        let name = dir.to_string_lossy().to_string();
+
+        // Split packages build several packages, any of them keeps the repository in use
+        let pkgnames = match Srcinfo::read(&repo_path.join(&dir)) {
+            Ok(srcinfo) if !srcinfo.pkgnames.is_empty() => srcinfo.pkgnames,
+            _ => vec![name.clone()],
+        };
+
+        let kind = if pkgnames.iter().any(|pkg| foreign.contains(pkg)) {
+            continue;
+        } else if pkgnames.iter().any(|pkg| installed.contains(pkg)) {
+            OrphanKind::OtherSource
+        } else {
+            OrphanKind::NotInstalled
+        };
+        orphans.push(Orphan { name, kind });
+    }
+    orphans.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    if out.is_json() {
+        out.json(&orphans)?;
+    } else if orphans.is_empty() {
+        println!("All cloned packages are installed");
+    } else {
+        let width = orphans
+            .iter()
+            .map(|o| o.name.len())
+            .max()
+            .unwrap_or_default();
+        for orphan in &orphans {
+            let note = match orphan.kind {
+                OrphanKind::NotInstalled => "not installed",
+                OrphanKind::OtherSource => "installed from the official repositories",
+            };
+            println!(
+                "{}{}{}{} {}",
+                out.fg(out.theme.package_name),
+                orphan.name,
+                out.paint(style::Reset),
+                padding(orphan.name.len(), width),
+                note
+            );
+        }
+    }
+
+    let removable: Vec<&str> = orphans
+        .iter()
+        .filter(|o| o.kind == OrphanKind::NotInstalled)
+        .map(|o| o.name.as_str())
+        .collect();
+    if !remove || removable.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    let question = format!(
+        "Remove {} {}?",
+        removable.len(),
+        repositories(removable.len())
+    );
+    if !confirm(&question, no_confirm)? {
+        println!("Nothing removed");
+        return Ok(());
+    }
+
+    // Like remove, don't leave frozen entries of deleted repositories behind
+    let mut frozen = Frozen::load(&proj_dirs)?;
+    for name in removable {
+        std::fs::remove_dir_all(repo_path.join(name))?;
+        frozen.thaw(name);
+        println!("Removed {}", name);
+    }
+    frozen.save(&proj_dirs)?;
+
+    Ok(())
+}
+
+/// Orders the given local packages so that dependencies are built first
+fn get_build_order(repo_path: &Path, package_names: &[String]) -> Result<Vec<String>, Error> {
+    let mut packages = BTreeMap::new();
+    for name in package_names {
+        let srcinfo = Srcinfo::read(&repo_path.join(name)).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Unable to read .SRCINFO of '{}': {}", name, e),
+            )
+        })?;
+        packages.insert(name.clone(), srcinfo);
+    }
+
+    depgraph::build_order(&packages)
+}
+
+fn print_phase(out: &Formatter, package_name: &str, phase: &str) {
+    println!(
+        "{}{}==>{} {}{}: {}{}",
+        out.paint(style::Bold),
+        out.fg(out.theme.success),
+        out.paint(color::Fg(color::Reset)),
+        package_name,
+        out.paint(style::Reset),
+        phase,
+        out.paint(style::Reset)
+    );
+}
+
+/// Asks the user a yes/no question before a destructive operation, defaulting to "no"
+///
+/// With `no_confirm` (`--no-confirm`), the answer is always yes. Without a terminal to ask,
+/// this fails instead of waiting for an answer that never comes.
+fn confirm(question: &str, no_confirm: bool) -> Result<bool, Error> {
+    if no_confirm {
+        return Ok(true);
+    }
+
+    if !termion::is_tty(&std::io::stdin()) {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "Not asking '{}' without a terminal, use --no-confirm to proceed",
+                question
+            ),
+        ));
+    }
+
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+async fn pull(
+    proj_dirs: ProjectDirs,
+    repos: Option<PathBuf>,
+    package_names: &[String],
+    options: PullOptions,
+    hook: Option<&String>,
+    auth: &RemoteAuth,
+    out: &Formatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut packages = Config::load(&proj_dirs)?.packages;
+    let repo_path = get_repo_path(proj_dirs.clone(), repos);
+    if !repo_path.exists() {
+        std::fs::create_dir_all(repo_path.as_ref())?;
+    }
+
+    let package_names = select_repos(
+        &proj_dirs,
+        &repo_path,
+        package_names,
+        options.all,
+        options.recursive_scan,
+        "pull",
+    )?;
+
+    // Reviews ask on the terminal, so they can't overlap
+    let review = options.review.then_some(Review {
+        out: *out,
+        no_confirm: options.no_confirm,
+    });
+    let jobs = match review {
+        Some(_) => 1,
+        None => options.jobs.max(1),
+    };
+
+    let total = package_names.len();
+    let policy = FailurePolicy::new(options.keep_going);
+    let pulls = package_names
+        .into_iter()
+        .take_while(|_| policy.proceed())
+        .map(|package_name| {
+            let path_base = repo_path.clone();
+            let package = packages.remove(&package_name).unwrap_or_default();
+            let auth = auth.clone();
+            task::spawn_blocking(move || {
+                let status = match pull_package(
+                    &path_base,
+                    &package_name,
+                    options.require_signatures,
+                    &package,
+                    review,
+                    &auth,
+                ) {
+                    Ok(status) => status,
+                    Err(e) => PullStatus::Failed {
+                        error: e.to_string(),
+                    },
+                };
+                PullResult {
+                    name: package_name,
+                    status,
+                }
+            })
+        });
+
+    let mut results = Vec::new();
+    let mut pulling = futures::stream::iter(pulls).buffer_unordered(jobs);
+    while let Some(joined) = pulling.next().await {
+        let result = joined?;
+        if let PullStatus::Failed { .. } = result.status {
+            policy.record_failure();
+        }
+        results.push(result);
+    }
+    drop(pulling);
+    results.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    // Hooks run one after another, so their output does not interleave either
+    if let Some(hook) = hook {
+        for result in &results {
+            if let PullStatus::Pulled { head, .. } = &result.status {
+                run_post_pull_hook(hook, &repo_path, &result.name, head);
+            }
+        }
+    }
+
+    print_pull_results(out, &results)?;
+
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.status, PullStatus::Failed { .. }))
+        .count();
+    if failed > 0 {
+        return Err(Box::new(policy.error(
+            format!("{} {} could not be pulled", failed, repositories(failed)),
+            total - results.len(),
+        )));
+    }
+
+    Ok(())
+}
+
+fn print_pull_results(
+    out: &Formatter,
+    results: &[PullResult],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if out.is_json() {
+        out.json(results)?;
+        return Ok(());
+    }
+
+    for result in results {
+        match &result.status {
+            PullStatus::Pulled { commits, audit, .. } => {
+                println!(
+                    "{}Pulled {}:{}",
+                    out.paint(style::Bold),
+                    result.name,
+                    out.paint(style::Reset)
+                );
+                println!();
+                for commit in commits {
+                    print_commit(out, commit);
+                }
+                if !audit.is_empty() {
+                    println!();
+                    print!("{}", format_findings(out, audit));
+                }
+                println!();
+            }
+            PullStatus::UpToDate => println!("{} is up to date", result.name),
+            PullStatus::Declined => println!("{} was not pulled", result.name),
+            PullStatus::Failed { error } => {
+                eprintln!("Error while pulling '{}': {}", result.name, error)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the repositories a command should work on: the given ones, or all with `--all`
+///
+/// `--all` leaves out the packages fetch and upgrade skip (see `is_excluded`), packages given
+/// by name are always used. Duplicates are removed, as working on the same repository twice at
+/// once would race on its working tree.
+fn select_repos(
+    proj_dirs: &ProjectDirs,
+    repo_path: &Path,
+    package_names: &[String],
+    all: bool,
+    recursive_scan: bool,
+    verb: &str,
+) -> Result<Vec<String>, Error> {
+    if all {
+        let frozen = Frozen::load(proj_dirs)?;
+        let config = Config::load(proj_dirs)?;
+        let ignored = TaurIgnore::load(repo_path)?;
+        return Ok(find_repos(repo_path, recursive_scan)?
+            .into_iter()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .filter(|name| !is_excluded(name, &frozen, &ignored, &config.package(name)))
+            .collect());
+    }
+
+    if package_names.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "No packages given, use 'taur {} --all' to {} all repositories",
+                verb, verb
+            ),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let package_names: Vec<String> = package_names
+        .iter()
+        .filter(|name| seen.insert(name.as_str()))
+        .cloned()
+        .collect();
+
+    let mut repos: Vec<String> = Vec::new();
+    let mut missing: Vec<&str> = Vec::new();
+    for name in &package_names {
+        match find_package_repo(repo_path, name) {
+            Some(repo) if !repos.contains(&repo) => repos.push(repo),
+            Some(_) => {}
+            None => missing.push(name),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("No local repositories for: {}", missing.join(", ")),
+        ));
+    }
+
+    Ok(repos)
+}
+
+/// Returns the repository of the given package, which is the one of its package base for split
+/// packages (like `python-foo` for `python-foo-docs`)
+///
+/// Split package names are looked up in the `.SRCINFO` of all repositories, so they keep
+/// working without remembering which package base they were cloned as.
+fn find_package_repo(repo_path: &Path, package_name: &str) -> Option<String> {
+    if repo_path.join(package_name).is_dir() {
+        return Some(package_name.to_string());
+    }
+
+    find_repos(repo_path, false)
+        .ok()?
+        .into_iter()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .find(|dir| {
+            Srcinfo::read(&repo_path.join(dir))
+                .is_ok_and(|srcinfo| srcinfo.pkgnames.iter().any(|name| name == package_name))
+        })
+}
+
+/// Returns the repository of the given package like `find_package_repo`, failing if it is not
+/// cloned
+fn cloned_repo(repo_path: &Path, package_name: &str) -> Result<String, Error> {
+    find_package_repo(repo_path, package_name).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("'{}' is not cloned", package_name),
+        )
+    })
+}
+
+/// Fast-forwards the given package to its upstream
+///
+/// Nothing is printed, so this can run for several packages at once.
+fn pull_package(
+    repo_path: &Path,
+    package_name: &str,
+    require_signatures: bool,
+    package: &PackageConfig,
+    review: Option<Review>,
+    auth: &RemoteAuth,
+) -> Result<PullStatus, Box<dyn std::error::Error>> {
+    let full_path = repo_path.join(package_name);
+
+    let repo = Repository::open(&full_path)?;
+    let upstream = Upstream::of(&repo, package);
+    restore_local_branch(&repo, &upstream)?;
+
+    let update_info = check_repo_updates(
+        full_path,
+        None,
+        FetchMode::Always,
+        require_signatures,
+        package,
+        auth,
+    )?;
+
+    let (commits, findings) = match update_info {
+        Some(update_info) if update_info.ahead > 0 => {
+            return Err(Box::new(Error::other(format!(
+                "{} local {} not upstream, a pull would not fast-forward",
+                update_info.ahead,
+                if update_info.ahead == 1 {
+                    "commit"
+                } else {
+                    "commits"
+                }
+            ))));
+        }
+        Some(update_info)
+            if update_info
+                .signatures
+                .iter()
+                .any(|s| *s != SignatureStatus::Verified) =>
+        {
+            let bad = update_info
+                .signatures
+                .iter()
+                .filter(|s| **s != SignatureStatus::Verified)
+                .count();
+            return Err(Box::new(Error::other(format!(
+                "{} incoming {} without a verified signature, not pulling",
+                bad,
+                if bad == 1 { "commit" } else { "commits" }
+            ))));
+        }
+        Some(update_info) => (update_info.commits, update_info.audit),
+        None => return Ok(PullStatus::UpToDate),
+    };
+
+    // The upstream was just fetched and compared with, unlike FETCH_HEAD it is always there
+    let upstream = repo
+        .revparse_single(&upstream.refname())?
+        .peel_to_commit()?;
+
+    // The checkout is forced, so it would silently throw away changes made with `taur edit`
+    if repo.config()?.get_bool(EDITED_KEY).unwrap_or(false) {
+        let changed = changed_files(&repo)?;
+        if !changed.is_empty() {
+            return Err(Box::new(Error::other(format!(
+                "local changes to {} made with 'taur edit' would be overwritten, commit or \
+                 discard them first",
+                changed.join(", ")
+            ))));
+        }
+    }
+
+    if let Some(review) = review {
+        if !review_build_files(&repo, package_name, &upstream, &findings, review)? {
+            return Ok(PullStatus::Declined);
+        }
+    }
+
+    // Fast-forward the checked out branch, which is not necessarily master after --branch
+    let mut branch_ref = repo.head()?.resolve()?;
+
+    let name = match branch_ref.name() {
+        Some(name) => name.to_string(),
+        None => String::from_utf8_lossy(branch_ref.name_bytes()).to_string(),
+    };
+
+    let msg = format!("Fast-Forward: Setting {} to id: {}", name, upstream.id());
+    branch_ref.set_target(upstream.id(), &msg)?;
+
+    repo.set_head(&name)?;
+
+    let checkout = &mut git2::build::CheckoutBuilder::default();
+    checkout.force();
+    repo.checkout_head(Some(checkout))?;
+
+    Ok(PullStatus::Pulled {
+        head: upstream.id().to_string(),
+        commits,
+        audit: findings,
+    })
+}
+
+/// Shows the changes to PKGBUILD and install files between HEAD and `upstream` and asks
+/// whether to pull them
+///
+/// The findings of the audit are shown above the changes. Returns true without asking if none
+/// of these files changed.
+fn review_build_files(
+    repo: &Repository,
+    package_name: &str,
+    upstream: &git2::Commit,
+    findings: &[Finding],
+    review: Review,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let out = &review.out;
+    let local = repo.head()?.peel_to_commit()?;
+
+    let mut options = git2::DiffOptions::new();
+    options.pathspec("PKGBUILD").pathspec("*.install");
+    let diff = repo.diff_tree_to_tree(
+        Some(&local.tree()?),
+        Some(&upstream.tree()?),
+        Some(&mut options),
+    )?;
+    let patch = format_patch(&diff, out)?;
+    if patch.is_empty() && findings.is_empty() {
+        return Ok(true);
+    }
+
+    // stdout is reserved for the results with JSON output
+    let mut stream: Box<dyn Write> = match out.is_json() {
+        true => Box::new(std::io::stderr()),
+        false => Box::new(std::io::stdout()),
+    };
+    writeln!(
+        stream,
+        "{}Changes to the build files of {}:{}",
+        out.paint(style::Bold),
+        package_name,
+        out.paint(style::Reset)
+    )?;
+    writeln!(stream)?;
+    if !findings.is_empty() {
+        write!(stream, "{}", format_findings(out, findings))?;
+        writeln!(stream)?;
+    }
+    write!(stream, "{}", patch)?;
+    writeln!(stream)?;
+    stream.flush()?;
+
+    let question = format!("Pull '{}'?", package_name);
+    Ok(confirm(&question, review.no_confirm)?)
+}
+
+/// Recreates the checked out branch from the package's upstream if it is missing
+///
+/// HEAD then still points to the branch, so every lookup of HEAD fails until it exists again.
+fn restore_local_branch(
+    repo: &Repository,
+    upstream: &Upstream,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match repo.head() {
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {}
+        _ => return Ok(()),
+    }
+
+    let head = repo.find_reference("HEAD")?;
+    let refname = head
+        .symbolic_target()
+        .ok_or("HEAD does not point to a branch")?;
+    let name = refname.strip_prefix("refs/heads/").unwrap_or(refname);
+    let tracking = format!("{}/{}", upstream.remote, upstream.branch);
+    let commit = match repo.find_reference(&upstream.refname()) {
+        Ok(reference) => reference.peel_to_commit()?,
+        Err(e) if e.code() == ErrorCode::NotFound => {
+            return Err(Box::new(Error::other(format!(
+                "The checked out branch '{}' is missing and there is no '{}' to restore it from",
+                name, tracking
+            ))));
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let mut branch = repo.branch(name, &commit, false)?;
+    branch.set_upstream(Some(&tracking))?;
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+
+    Ok(())
+}
+
+/// Runs the post-pull hook inside the repository of a freshly pulled package
+///
+/// A failing hook is only reported, so it does not abort pulling the remaining packages.
+fn run_post_pull_hook(hook: &str, repo_path: &Path, package_name: &str, head: &str) {
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .arg("sh")
+        .arg(package_name)
+        .arg(head)
+        .current_dir(repo_path.join(package_name))
+        .env("TAUR_PACKAGE", package_name)
+        .env("TAUR_HEAD", head)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Post-pull hook for '{}' failed: {}", package_name, status),
+        Err(e) => eprintln!("Unable to run post-pull hook for '{}': {}", package_name, e),
+    }
+}
+
+fn parse_regex(pattern: &str) -> Result<Regex, String> {
+    // The build error itself only says which pattern failed, the syntax error explains why
+    Regex::new(pattern).map_err(|e| match std::error::Error::source(&e) {
+        Some(source) => source.to_string(),
+        None => e.to_string(),
+    })
+}
+
+/// Checks that a profile name can be used as a directory name
+fn parse_profile(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(std::path::is_separator) {
+        return Err(String::from(
+            "profile names must not be empty, start with a dot or contain a path separator",
+        ));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Returns the repository directory: the given one, `AURDEST` (as used by paru and yay) or the
+/// default inside the data directory
+fn get_repo_path(proj_dirs: ProjectDirs, repos: Option<PathBuf>) -> Box<PathBuf> {
+    Box::new(choose_repo_path(
+        proj_dirs.data_dir(),
+        repos,
+        std::env::var_os("AURDEST"),
+    ))
+}
+
+/// Picks the repository directory from `--repos`, the value of `AURDEST` and the data directory
+fn choose_repo_path(data_dir: &Path, repos: Option<PathBuf>, aurdest: Option<OsString>) -> PathBuf {
+    if let Some(s) = repos {
+        return s;
+    }
+
+    match aurdest.filter(|dest| !dest.is_empty()) {
+        Some(dest) => PathBuf::from(dest),
+        None => data_dir.join("repos"),
+    }
+}
+
+fn print_update_info(
+    out: &Formatter,
+    mut update_infos: Vec<UpdateInfo>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_infos.sort_unstable();
+
+    if out.is_json() {
+        out.json(&update_infos)?;
+    } else if !update_infos.is_empty() {
+        println!(
+            "{}The following packages have upstream changes:{}",
+            out.paint(style::Bold),
+            out.paint(style::Reset)
+        );
+        println!();
+
+        for info in update_infos {
+            info.print(out);
+        }
+    } else {
+        println!("There are currently no packages with upstream changes");
+    }
+
+    Ok(())
+}
+
+/// Prints one line per repository with upstream changes, like `foo ↓7 ↑0`
+fn print_update_summary(
+    out: &Formatter,
+    mut update_infos: Vec<UpdateInfo>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_infos.sort_unstable();
+
+    if out.is_json() {
+        out.json(&update_infos)?;
+        return Ok(());
+    }
+
+    if update_infos.is_empty() {
+        println!("There are currently no packages with upstream changes");
+        return Ok(());
+    }
+
+    let width = update_infos.iter().map(|i| i.name.len()).max().unwrap_or(0);
+    for info in update_infos {
+        println!(
+            "{}{}{} {}{}↓{} ↑{}{}",
+            out.fg(out.theme.package_name),
+            info.name,
+            out.paint(style::Reset),
+            padding(info.name.len(), width),
+            out.fg(out.theme.commit),
+            info.behind(),
+            info.ahead,
+            out.paint(style::Reset)
+        );
+    }
+
+    Ok(())
+}
+
+/// Keeps only the commits whose message matches the pattern, dropping repositories without any
+///
+/// Commits left out by `--max-commits` were never read, so they can't match.
+fn grep_commits(update_infos: Vec<UpdateInfo>, pattern: &Regex) -> Vec<UpdateInfo> {
+    update_infos
+        .into_iter()
+        .filter_map(|mut info| {
+            let matches: Vec<bool> = info
+                .commits
+                .iter()
+                .map(|message| pattern.is_match(message))
+                .collect();
+            let mut keep = matches.iter();
+            info.commits.retain(|_| *keep.next().unwrap_or(&false));
+            if !info.signatures.is_empty() {
+                let mut keep = matches.iter();
+                info.signatures.retain(|_| *keep.next().unwrap_or(&false));
+            }
+            info.omitted = 0;
+
+            (!info.commits.is_empty()).then_some(info)
+        })
+        .collect()
+}
+
+/// Reminds that VCS packages can't be checked by fetching their AUR repository
+fn print_vcs(out: &Formatter, vcs: &[String]) {
+    if vcs.is_empty() || out.is_json() {
+        return;
+    }
+
+    println!();
+    println!(
+        "{}VCS packages (upstream tracked on build, they may be outdated without new commits):{} {}",
+        out.paint(style::Bold),
+        out.paint(style::Reset),
+        vcs.join(", ")
+    );
+}
+
+/// Prints the time each repository took, as sorted by `check_all_repos`, and the total
+fn print_timings(out: &Formatter, timings: &[(String, Duration)], total: Duration) {
+    let width = timings
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default()
+        .max(5);
+
+    println!();
+    println!(
+        "{}Timings:{}",
+        out.paint(style::Bold),
+        out.paint(style::Reset)
+    );
+    for (name, elapsed) in timings {
+        println!(
+            "  {}{} {:>7.2}s",
+            name,
+            padding(name.len(), width),
+            elapsed.as_secs_f64()
+        );
+    }
+    println!(
+        "  {}total{}{} {:>7.2}s",
+        out.paint(style::Bold),
+        out.paint(style::Reset),
+        padding(5, width),
+        total.as_secs_f64()
+    );
+}
+
+fn print_check_failures(
+    out: &Formatter,
+    skipped: &[String],
+    failures: &[(String, FailureKind, String)],
+) {
+    for warning in skipped {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if failures.is_empty() {
+        return;
+    }
+
+    // Keep stdout parseable when printing JSON
+    if out.is_json() {
+        for (name, _, reason) in failures {
+            eprintln!("Error while checking '{}': {}", name, reason);
+        }
+        return;
+    }
+
+    println!(
+        "{}{}Failed to check {} {}:{}",
+        out.paint(style::Bold),
+        out.fg(out.theme.warning),
+        failures.len(),
+        repositories(failures.len()),
+        out.paint(style::Reset)
+    );
+    println!();
+
+    for (name, _, reason) in failures {
+        println!(
+            "{}{}{}: {}",
+            out.fg(out.theme.package_name),
+            name,
+            out.paint(style::Reset),
+            reason
+        );
+    }
+}
+
+/// Counts the failures by cause and lists the repositories of each, so recurring problems
+/// stand out when many repositories failed
+fn print_failure_summary(out: &Formatter, failures: &[(String, FailureKind, String)]) {
+    if failures.len() < 2 || out.is_json() {
+        return;
+    }
+
+    let names_of = |kind: FailureKind| -> Vec<&str> {
+        failures
+            .iter()
+            .filter(|(_, k, _)| *k == kind)
+            .map(|(name, _, _)| name.as_str())
+            .collect()
+    };
+
+    let counts: Vec<String> = FailureKind::ALL
+        .iter()
+        .map(|kind| format!("{} {}", kind, names_of(*kind).len()))
+        .collect();
+    println!();
+    println!(
+        "{}Failures by cause:{} {}",
+        out.paint(style::Bold),
+        out.paint(style::Reset),
+        counts.join(", ")
+    );
+    for kind in FailureKind::ALL {
+        let names = names_of(kind);
+        if !names.is_empty() {
+            println!("  {}: {}", kind, names.join(", "));
+        }
+    }
+}
+
+/// Lists the repositories whose state was taken from an earlier fetch
+fn print_recent(out: &Formatter, recent: &[(String, u64)]) {
+    if recent.is_empty() || out.is_json() {
+        return;
+    }
+
+    println!(
+        "{}Not fetched again (checked recently):{}",
+        out.paint(style::Bold),
+        out.paint(style::Reset)
+    );
+    for (name, age) in recent {
+        println!(
+            "  {}{}{} (last checked {})",
+            out.fg(out.theme.package_name),
+            name,
+            out.paint(style::Reset),
+            format_age(*age)
+        );
+    }
+}
+
+/// Formats an age in seconds like "3 hours ago"
+fn format_age(secs: u64) -> String {
+    let (count, unit) = match secs {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Returns the spaces needed to fill a column of `width` after text of length `len`
+fn padding(len: usize, width: usize) -> String {
+    " ".repeat(width.saturating_sub(len))
+}
+
+fn repositories(count: usize) -> &'static str {
+    if count == 1 {
+        "repository"
+    } else {
+        "repositories"
+    }
+}
+
+fn first_line(message: &str) -> String {
+    message.lines().next().unwrap_or_default().to_string()
+}
+
+fn cloned_marker(out: &Formatter) -> String {
+    format!(
+        " {}{}[cloned]{}",
+        out.paint(style::Bold),
+        out.fg(out.theme.success),
+        out.paint(style::Reset)
+    )
+}
+
+fn print_commit(out: &Formatter, commit: &str) {
+    println!(
+        "{}* {}{}{}",
+        out.fg(out.theme.package_name),
+        out.fg(out.theme.commit),
+        commit,
+        out.paint(style::Reset)
+    );
+}
+
+fn print_signed_commit(out: &Formatter, commit: &str, status: SignatureStatus) {
+    let (label, color) = match status {
+        SignatureStatus::Verified => ("verified", out.theme.success),
+        SignatureStatus::Unverified => ("unverified", out.theme.warning),
+        SignatureStatus::Unsigned => ("unsigned", out.theme.warning),
+    };
+    println!(
+        "{}* {}[{}] {}{}{}",
+        out.fg(out.theme.package_name),
+        out.fg(color),
+        label,
+        out.fg(out.theme.commit),
+        commit,
+        out.paint(style::Reset)
+    );
+}
+
+fn check_repo_updates(
+    path: PathBuf,
+    max_commits: Option<usize>,
+    fetch: FetchMode,
+    verify_signatures: bool,
+    package: &PackageConfig,
+    auth: &RemoteAuth,
+) -> Result<Option<UpdateInfo>, Box<dyn std::error::Error>> {
+    let dir_name = path.file_name().ok_or("File name was None?!")?;
+    let dir_name = String::from(dir_name.to_string_lossy());
+
+    let repo = Repository::open(path)?;
+
+    // Without an upstream there is nothing to compare with, so don't bother fetching
+    let head = repo.head()?;
+    if head.is_branch() && !Upstream::is_overridden(package) {
+        let branch = Branch::wrap(head);
+        if let Err(e) = branch.upstream() {
+            if e.code() == ErrorCode::NotFound {
+                return Err(Box::new(NoUpstream {
+                    name: dir_name,
+                    branch: branch.name()?.unwrap_or("master").to_string(),
+                }));
+            }
+        }
+    }
+
+    let upstream = Upstream::of(&repo, package);
+    if fetch != FetchMode::Never {
+        let mut remote = repo.find_remote(&upstream.remote)?;
+        // pull needs the latest upstream, so it always fetches
+        let moved = fetch == FetchMode::Always
+            || remote::upstream_moved(&repo, &mut remote, &upstream, auth)
+                .map_err(remote::explain)?;
+        if moved {
+            remote
+                .fetch(&[&upstream.branch], Some(&mut auth.fetch_options()), None)
+                .map_err(remote::explain)?;
+        }
+    }
+
+    let local_rev = repo.revparse_single("HEAD")?;
+    let remote_rev = repo.revparse_single(&upstream.refname())?;
+
+    if local_rev.id() != remote_rev.id() {
+        let (ahead, behind) = repo.graph_ahead_behind(local_rev.id(), remote_rev.id())?;
+
+        // Only the counts are wanted, so skip setting up the revwalk
+        if max_commits == Some(0) {
+            return Ok(Some(UpdateInfo {
+                name: dir_name,
+                commits: Vec::new(),
+                signatures: Vec::new(),
+                omitted: behind,
+                ahead,
+                audit: Vec::new(),
+            }));
+        }
+
+        let mut revwalk = repo.revwalk()?;
+
+        revwalk.push(remote_rev.id())?;
+        revwalk.hide(local_rev.id())?;
+
+        let mut commits: Vec<String> = Vec::new();
+        let mut signatures: Vec<SignatureStatus> = Vec::new();
+        let mut omitted = 0;
+
+        for oid in revwalk {
+            if max_commits.is_some_and(|max| commits.len() >= max) {
+                omitted = behind.saturating_sub(commits.len());
+                break;
+            }
+
+            let oid = oid?;
+            let commit = repo.find_commit(oid)?;
+            let message = match commit.message() {
+                Some(c) => String::from(c),
+                None => String::from_utf8_lossy(commit.message_bytes()).to_string(),
+            };
+            commits.push(message);
+            if verify_signatures {
+                signatures.push(signature::verify(&repo, oid));
+            }
+        }
+
+        // Compare with the merge base, so local commits don't show up as removed lines
+        let base = repo
+            .merge_base(local_rev.id(), remote_rev.id())
+            .unwrap_or(local_rev.id());
+        let base_tree = repo.find_commit(base)?.tree()?;
+        let remote_tree = remote_rev.peel_to_tree()?;
+        let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&remote_tree), None)?;
+
+        return Ok(Some(UpdateInfo {
+            name: dir_name,
+            commits,
+            signatures,
+            omitted,
+            ahead,
+            audit: audit::audit(&diff)?,
+        }));
     }
 
     Ok(None)
 }
 
+fn get_cloned_packages(repo_path: &Path) -> Result<HashSet<OsString>, Error> {
+    if !repo_path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    Ok(get_dir_list(repo_path)?.into_iter().collect())
+}
+
+/// Returns the repositories inside the repos directory, relative to it
+///
+/// By default only the directories at the top level are taken. With `recursive`, directories
+/// without a `.git` are searched for repositories as well, so they can be grouped like
+/// `games/foo`. Hidden directories and symlinks are not descended into.
+fn find_repos(repo_path: &Path, recursive: bool) -> Result<Vec<OsString>, Error> {
+    if !recursive {
+        return get_dir_list(repo_path);
+    }
+
+    let mut repos = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in std::fs::read_dir(repo_path.join(&relative))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if !entry.path().is_dir() || name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = relative.join(&name);
+            if entry.path().join(".git").exists() {
+                repos.push(path.into_os_string());
+            } else if !entry.file_type()?.is_symlink() {
+                pending.push(path);
+            }
+        }
+    }
+
+    repos.sort_unstable();
+    Ok(repos)
+}
+
 fn get_dir_list(pathbuf: &Path) -> Result<Vec<OsString>, Error> {
     let path = Path::new(pathbuf);
     let path_iter = std::fs::read_dir(path)?;
@@ -439,3 +6816,485 @@ fn get_dir_list(pathbuf: &Path) -> Result<Vec<OsString>, Error> {
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use git2::{Oid, RepositoryInitOptions, Signature};
+
+    use super::*;
+
+    /// Creates a repository with `master` checked out and a single commit
+    fn init_repo(path: &Path) -> Repository {
+        let repo = Repository::init_opts(path, RepositoryInitOptions::new().initial_head("master"))
+            .unwrap();
+        commit_file(&repo, "PKGBUILD", "pkgname=foo\n", "Initial commit");
+        repo
+    }
+
+    /// Writes a file into the working tree and commits it on the checked out branch
+    fn commit_file(repo: &Repository, name: &str, content: &str, message: &str) -> Oid {
+        std::fs::write(repo.workdir().unwrap().join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Packager", "packager@example.org").unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    /// Clones the repository at `upstream` like `taur clone` does
+    fn clone_from(upstream: &Path, path: &Path) -> Repository {
+        Repository::clone(upstream.to_str().unwrap(), path).unwrap()
+    }
+
+    fn check(
+        path: &Path,
+        fetch: FetchMode,
+    ) -> Result<Option<UpdateInfo>, Box<dyn std::error::Error>> {
+        check_repo_updates(
+            path.to_path_buf(),
+            None,
+            fetch,
+            false,
+            &PackageConfig::default(),
+            &RemoteAuth::default(),
+        )
+    }
+
+    #[test]
+    fn clone_target_may_be_missing_or_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("foo");
+        assert!(prepare_clone_target(&target, "foo", false).is_ok());
+
+        std::fs::create_dir(&target).unwrap();
+        assert!(prepare_clone_target(&target, "foo", false).is_ok());
+        assert!(target.is_dir());
+    }
+
+    #[test]
+    fn clone_target_with_other_files_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("foo");
+        std::fs::create_dir(&target).unwrap();
+        std::fs::write(target.join("notes.txt"), "keep me").unwrap();
+
+        let error = prepare_clone_target(&target, "foo", false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert!(error.to_string().contains("is no valid repository"));
+        assert!(error.to_string().contains("taur clone --force foo"));
+        assert!(target.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn cloned_target_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("foo");
+        Repository::init(&target).unwrap();
+
+        let error = prepare_clone_target(&target, "foo", false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert!(error.to_string().contains("already cloned"));
+        assert!(Repository::open(&target).is_ok());
+
+        assert!(prepare_clone_target(&target, "foo", true).is_ok());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn repo_without_upstream_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo");
+        init_repo(&path);
+
+        let error = check(&path, FetchMode::Always).err().unwrap();
+        let no_upstream = error.downcast_ref::<NoUpstream>().unwrap();
+        assert_eq!(no_upstream.name, "foo");
+        assert_eq!(no_upstream.branch, "master");
+    }
+
+    #[test]
+    fn no_upstream_hint_names_the_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo");
+        let repo = init_repo(&path);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("stable", &head, false).unwrap();
+        repo.set_head("refs/heads/stable").unwrap();
+
+        let error = check(&path, FetchMode::Always).err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("'git branch --set-upstream-to=origin/stable stable'"));
+    }
+
+    #[test]
+    fn fresh_clone_has_no_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(&dir.path().join("upstream"));
+        let path = dir.path().join("foo");
+        clone_from(&dir.path().join("upstream"), &path);
+
+        assert!(check(&path, FetchMode::Never).unwrap().is_none());
+        assert!(check(&path, FetchMode::Always).unwrap().is_none());
+    }
+
+    #[test]
+    fn commit_message_that_is_no_utf8_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = init_repo(&dir.path().join("upstream"));
+        let path = dir.path().join("foo");
+        clone_from(&dir.path().join("upstream"), &path);
+
+        // git2 only commits valid UTF-8, so write the commit object by hand
+        let parent = commit_file(&upstream, "PKGBUILD", "pkgname=foo\npkgrel=2\n", "Bump");
+        let tree = upstream.find_commit(parent).unwrap().tree_id();
+        let mut commit = format!(
+            "tree {}\nparent {}\nauthor Packager <packager@example.org> 0 +0000\n\
+             committer Packager <packager@example.org> 0 +0000\n\nUpdate to 1.1 ",
+            tree, parent
+        )
+        .into_bytes();
+        commit.extend_from_slice(b"\xe4\xf6\xfc\n");
+        let oid = upstream
+            .odb()
+            .unwrap()
+            .write(git2::ObjectType::Commit, &commit)
+            .unwrap();
+        upstream
+            .reference(
+                "refs/heads/master",
+                oid,
+                true,
+                "Commit with a Latin-1 message",
+            )
+            .unwrap();
+
+        let info = check(&path, FetchMode::Always).unwrap().unwrap();
+        assert_eq!(info.behind(), 2);
+        assert_eq!(info.commits.len(), 2);
+        assert_eq!(info.commits[0], "Update to 1.1 \u{fffd}\u{fffd}\u{fffd}\n");
+        assert_eq!(info.commits[1], "Bump");
+    }
+
+    #[test]
+    fn missing_local_branch_is_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = init_repo(&dir.path().join("upstream"));
+        let path = dir.path().join("foo");
+        let repo = clone_from(&dir.path().join("upstream"), &path);
+        let tip = commit_file(&upstream, "PKGBUILD", "pkgname=foo\npkgrel=2\n", "Bump");
+        repo.find_remote("origin")
+            .unwrap()
+            .fetch(&["master"], None, None)
+            .unwrap();
+        repo.find_reference("refs/heads/master")
+            .unwrap()
+            .delete()
+            .unwrap();
+        assert_eq!(repo.head().err().unwrap().code(), ErrorCode::UnbornBranch);
+
+        let upstream = Upstream::of(&repo, &PackageConfig::default());
+        restore_local_branch(&repo, &upstream).unwrap();
+
+        assert_eq!(repo.head().unwrap().target(), Some(tip));
+        let branch = repo.find_branch("master", git2::BranchType::Local).unwrap();
+        assert_eq!(
+            branch.upstream().unwrap().name().unwrap(),
+            Some("origin/master")
+        );
+        assert!(check(&path, FetchMode::Never).unwrap().is_none());
+    }
+
+    #[test]
+    fn existing_local_branch_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(&dir.path().join("upstream"));
+        let path = dir.path().join("foo");
+        let repo = clone_from(&dir.path().join("upstream"), &path);
+        let local = commit_file(&repo, "notes.txt", "local change\n", "Local commit");
+
+        let upstream = Upstream::of(&repo, &PackageConfig::default());
+        restore_local_branch(&repo, &upstream).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(local));
+    }
+
+    #[test]
+    fn missing_local_branch_is_restored_from_configured_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = init_repo(&dir.path().join("upstream"));
+        let stable = upstream.head().unwrap().peel_to_commit().unwrap();
+        upstream.branch("stable", &stable, false).unwrap();
+        commit_file(&upstream, "PKGBUILD", "pkgname=foo\npkgrel=2\n", "Bump");
+        let path = dir.path().join("foo");
+        let repo = clone_from(&dir.path().join("upstream"), &path);
+        repo.find_reference("refs/heads/master")
+            .unwrap()
+            .delete()
+            .unwrap();
+        let package = PackageConfig {
+            branch: Some(String::from("stable")),
+            ..PackageConfig::default()
+        };
+
+        restore_local_branch(&repo, &Upstream::of(&repo, &package)).unwrap();
+
+        assert_eq!(repo.head().unwrap().target(), Some(stable.id()));
+        assert_eq!(remote::tracked_branch(&repo), "stable");
+    }
+
+    #[test]
+    fn repo_with_missing_objects_is_damaged() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(&dir.path().join("upstream"));
+        let path = dir.path().join("foo");
+        clone_from(&dir.path().join("upstream"), &path);
+        std::fs::remove_dir_all(path.join(".git/objects")).unwrap();
+        std::fs::create_dir(path.join(".git/objects")).unwrap();
+
+        let error = check(&path, FetchMode::Never).err().unwrap();
+        assert!(corruption(error.as_ref()).is_some());
+        assert_eq!(FailureKind::of(error.as_ref()), FailureKind::Damaged);
+    }
+
+    #[test]
+    fn directory_without_repo_is_damaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo");
+        std::fs::create_dir(&path).unwrap();
+
+        let error = check(&path, FetchMode::Never).err().unwrap();
+        assert_eq!(FailureKind::of(error.as_ref()), FailureKind::Damaged);
+    }
+
+    #[test]
+    fn other_errors_are_not_damaged() {
+        let network = Error::other(git2::Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Net,
+            "failed to resolve address",
+        ));
+        assert!(corruption(&network).is_none());
+        assert_eq!(FailureKind::of(&network), FailureKind::Network);
+
+        let config = git2::Error::new(ErrorCode::GenericError, ErrorClass::Config, "bad config");
+        assert!(corruption(&config).is_none());
+        assert_eq!(FailureKind::of(&config), FailureKind::Other);
+    }
+
+    /// Creates the repository of a package base building `python-foo` and `python-foo-docs`
+    fn split_package(repo_path: &Path) -> PathBuf {
+        let path = repo_path.join("python-foo");
+        let repo = init_repo(&path);
+        let srcinfo = "pkgbase = python-foo\n\tpkgver = 1.0\n\tpkgrel = 1\n\n\
+                       pkgname = python-foo\n\npkgname = python-foo-docs\n";
+        commit_file(&repo, ".SRCINFO", srcinfo, "Add .SRCINFO");
+        path
+    }
+
+    #[test]
+    fn split_package_is_built_in_its_package_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = split_package(dir.path());
+
+        // Records where it was run, and lists a package file like makepkg --packagelist
+        let makepkg = dir.path().join("makepkg");
+        std::fs::write(
+            &makepkg,
+            "#!/bin/sh\nfile=\"$PWD/python-foo-docs-1.0-1-any.pkg.tar.zst\"\n\
+             if [ \"$1\" = --packagelist ]; then echo \"$file\"; else touch \"$file\"; fi\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&makepkg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let options = BuildOptions {
+            tools: Tools {
+                makepkg,
+                ..Tools::default()
+            },
+            ..BuildOptions::default()
+        };
+
+        let files = build_cloned(dir.path(), "python-foo-docs", &options).unwrap();
+        assert_eq!(
+            files,
+            vec![base.join("python-foo-docs-1.0-1-any.pkg.tar.zst")]
+        );
+        assert!(!dir.path().join("python-foo-docs").exists());
+    }
+
+    #[test]
+    fn split_package_is_found_by_which() {
+        let dir = tempfile::tempdir().unwrap();
+        split_package(dir.path());
+        let proj_dirs = ProjectDirs::from("", "", "taur").unwrap();
+        let repos = Some(dir.path().to_path_buf());
+
+        assert!(which(proj_dirs.clone(), repos.clone(), "python-foo-docs", true).is_ok());
+        assert_eq!(
+            cloned_repo(dir.path(), "python-foo-docs").unwrap(),
+            "python-foo"
+        );
+        let error = which(proj_dirs, repos, "python-bar", false).unwrap_err();
+        assert_eq!(error.to_string(), "'python-bar' is not cloned");
+    }
+
+    #[test]
+    fn untracked_package_is_excluded() {
+        let dir = tempfile::tempdir().unwrap();
+        TaurIgnore::add(dir.path(), "foo[bar]").unwrap();
+        let ignored = TaurIgnore::load(dir.path()).unwrap();
+        let frozen = Frozen::default();
+        let package = PackageConfig::default();
+
+        assert!(is_excluded("foo[bar]", &frozen, &ignored, &package));
+        assert!(!is_excluded("foob", &frozen, &ignored, &package));
+    }
+
+    #[test]
+    fn frozen_and_config_ignored_packages_are_not_included_again() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".taurignore"), "*\n!foo\n!bar\n!baz\n").unwrap();
+        let ignored = TaurIgnore::load(dir.path()).unwrap();
+        let mut frozen = Frozen::default();
+        frozen.freeze("bar");
+        let ignore = PackageConfig {
+            ignore: true,
+            ..PackageConfig::default()
+        };
+
+        assert!(!is_excluded(
+            "foo",
+            &frozen,
+            &ignored,
+            &PackageConfig::default()
+        ));
+        assert!(is_excluded(
+            "bar",
+            &frozen,
+            &ignored,
+            &PackageConfig::default()
+        ));
+        assert!(is_excluded("baz", &frozen, &ignored, &ignore));
+        assert!(is_excluded(
+            "qux",
+            &frozen,
+            &ignored,
+            &PackageConfig::default()
+        ));
+    }
+
+    #[test]
+    fn upgrade_without_build_order_reports_the_pulled_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(&dir.path().join("upstream"));
+        clone_from(&dir.path().join("upstream"), &dir.path().join("repos/foo"));
+        let packages = vec![(String::from("foo"), PackageConfig::default())];
+        let options = UpgradeOptions {
+            selected: Vec::new(),
+            dry_run: false,
+            keep_going: false,
+            review: false,
+            recursive_scan: false,
+            build: BuildOptions::default(),
+        };
+        let out = Formatter::new(OutputFormat::Plain, false, Theme::default());
+
+        let error = upgrade_packages(
+            &dir.path().join("repos"),
+            &packages,
+            &options,
+            &FailurePolicy::new(false),
+            None,
+            &RemoteAuth::default(),
+            &out,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "1 package could not be upgraded");
+    }
+
+    fn config_with_default(line: &str) -> Config {
+        Config {
+            default_command: Some(line.to_string()),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn default_command_global_options_are_merged() {
+        let config = config_with_default("list --json --color never -y --output-dir out");
+        let (command, defaults) = default_command_of(&config).unwrap();
+        assert!(matches!(command, Command::List { .. }));
+
+        let mut args = Args::parse_from(["taur", "--output", "pretty", "--repos", "/aur"]);
+        merge_defaults(&mut args, defaults);
+        assert_eq!(args.output, Some(OutputFormat::Pretty));
+        assert_eq!(args.repos, Some(PathBuf::from("/aur")));
+        assert!(matches!(args.color, Some(ColorChoice::Never)));
+        assert!(args.no_confirm);
+        assert_eq!(args.output_dir, Some(PathBuf::from("out")));
+
+        let (_, defaults) = default_command_of(&config).unwrap();
+        let mut args = Args::parse_from(["taur"]);
+        merge_defaults(&mut args, defaults);
+        assert_eq!(args.output, Some(OutputFormat::Json));
+    }
+
+    #[test]
+    fn default_command_cannot_be_config() {
+        for line in ["config path", "fetch --profile work"] {
+            let error = default_command_of(&config_with_default(line))
+                .err()
+                .unwrap();
+            assert!(error
+                .to_string()
+                .starts_with(&format!("Invalid default_command '{}': ", line)));
+        }
+    }
+
+    #[test]
+    fn repo_path_from_aurdest() {
+        let data_dir = Path::new("/data/taur");
+        assert_eq!(
+            choose_repo_path(data_dir, None, Some(OsString::from("/aur"))),
+            PathBuf::from("/aur")
+        );
+    }
+
+    #[test]
+    fn repo_path_without_aurdest() {
+        let data_dir = Path::new("/data/taur");
+        assert_eq!(
+            choose_repo_path(data_dir, None, None),
+            PathBuf::from("/data/taur/repos")
+        );
+        assert_eq!(
+            choose_repo_path(data_dir, None, Some(OsString::new())),
+            PathBuf::from("/data/taur/repos")
+        );
+    }
+
+    #[test]
+    fn repos_option_overrides_aurdest() {
+        let data_dir = Path::new("/data/taur");
+        assert_eq!(
+            choose_repo_path(
+                data_dir,
+                Some(PathBuf::from("/repos")),
+                Some(OsString::from("/aur"))
+            ),
+            PathBuf::from("/repos")
+        );
+    }
+}
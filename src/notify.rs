@@ -0,0 +1,62 @@
+// notify.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind};
+use std::process::Command;
+
+/// Number of package names listed in a notification, the rest are only counted
+const MAX_NAMES: usize = 10;
+
+/// Sends a desktop notification about the packages with upstream changes
+///
+/// Uses `notify-send` from libnotify, which talks to whatever notification daemon runs.
+pub fn updates(package_names: &[&str]) -> Result<(), Error> {
+    let summary = match package_names.len() {
+        1 => String::from("1 AUR package has updates"),
+        count => format!("{} AUR packages have updates", count),
+    };
+    let mut body = package_names
+        .iter()
+        .take(MAX_NAMES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if package_names.len() > MAX_NAMES {
+        body.push_str(&format!(" and {} more", package_names.len() - MAX_NAMES));
+    }
+
+    let status = Command::new("notify-send")
+        .arg("--app-name=taur")
+        .arg(summary)
+        .arg(body)
+        .status()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::new(
+                ErrorKind::NotFound,
+                "notify-send not found, install libnotify",
+            ),
+            _ => e,
+        })?;
+
+    if !status.success() {
+        return Err(Error::other(format!("notify-send failed: {}", status)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,307 @@
+// output.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::{Error, ErrorKind};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use termion::color::{self, AnsiValue};
+
+use crate::config::ColorConfig;
+
+/// Output formats selectable with `--output`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable output
+    #[default]
+    Pretty,
+    /// Human-readable output without escape sequences
+    Plain,
+    /// Machine-readable JSON
+    Json,
+}
+
+/// When to use colors, selectable with `--color` or the `color` setting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Use colors if stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    /// Always use colors, even if NO_COLOR is set or stdout is piped
+    Always,
+    /// Never use colors
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolves the choice for the current environment
+    pub fn enabled(self) -> bool {
+        self.resolve(
+            std::env::var_os("NO_COLOR").as_deref(),
+            termion::is_tty(&std::io::stdout()),
+        )
+    }
+
+    /// Resolves the choice for the given value of `NO_COLOR` and whether stdout is a terminal
+    fn resolve(self, no_color: Option<&OsStr>, is_tty: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = no_color.is_some_and(|v| !v.is_empty());
+                !no_color && is_tty
+            }
+        }
+    }
+}
+
+/// Compact formats for status bars, selectable with `fetch --status-format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StatusFormat {
+    /// JSON object for waybar's custom modules (also understood by polybar scripts)
+    Waybar,
+}
+
+/// Update status in the format of waybar's custom modules
+#[derive(Debug, Serialize)]
+pub struct WaybarStatus {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<String>,
+    pub class: &'static str,
+}
+
+impl WaybarStatus {
+    pub fn new(package_names: &[&str]) -> WaybarStatus {
+        if package_names.is_empty() {
+            return WaybarStatus {
+                text: String::new(),
+                tooltip: None,
+                class: "ok",
+            };
+        }
+
+        WaybarStatus {
+            text: package_names.len().to_string(),
+            tooltip: Some(package_names.join("\n")),
+            class: "updates",
+        }
+    }
+
+    /// Prints the status on a single line, as waybar reads one object per line
+    pub fn print(&self) -> Result<(), serde_json::Error> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+/// Colors used for the different parts of the output
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub header: AnsiValue,
+    pub package_name: AnsiValue,
+    pub commit: AnsiValue,
+    pub warning: AnsiValue,
+    pub success: AnsiValue,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            header: AnsiValue(4),
+            package_name: AnsiValue(5),
+            commit: AnsiValue(6),
+            warning: AnsiValue(3),
+            success: AnsiValue(2),
+        }
+    }
+}
+
+impl Theme {
+    /// Builds a theme from the `[colors]` config section, using defaults for unset roles
+    pub fn from_config(colors: &ColorConfig) -> Result<Theme, Error> {
+        let default = Theme::default();
+        let pick = |name: &Option<String>, default: AnsiValue| match name {
+            Some(name) => parse_color(name),
+            None => Ok(default),
+        };
+
+        Ok(Theme {
+            header: pick(&colors.header, default.header)?,
+            package_name: pick(&colors.package_name, default.package_name)?,
+            commit: pick(&colors.commit, default.commit)?,
+            warning: pick(&colors.warning, default.warning)?,
+            success: pick(&colors.success, default.success)?,
+        })
+    }
+}
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Parses a color name like `blue` or `light-red`, or an ANSI color number (0-255)
+fn parse_color(name: &str) -> Result<AnsiValue, Error> {
+    if let Ok(value) = name.parse::<u8>() {
+        return Ok(AnsiValue(value));
+    }
+
+    let (base, offset) = match name.strip_prefix("light-") {
+        Some(base) => (base, 8),
+        None => (name, 0),
+    };
+
+    match COLOR_NAMES.iter().position(|&c| c == base) {
+        Some(index) => Ok(AnsiValue(index as u8 + offset)),
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Unknown color '{}' (expected one of {}, their light- variants or 0-255)",
+                name,
+                COLOR_NAMES.join(", ")
+            ),
+        )),
+    }
+}
+
+/// Emits command results in the format selected by the user
+#[derive(Clone, Copy, Debug)]
+pub struct Formatter {
+    format: OutputFormat,
+    color: bool,
+    pub theme: Theme,
+}
+
+impl Formatter {
+    /// Without `color`, pretty output is printed like plain output
+    pub fn new(format: OutputFormat, color: bool, theme: Theme) -> Self {
+        Formatter {
+            format,
+            color,
+            theme,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Returns true if escape sequences may be printed
+    pub fn is_pretty(&self) -> bool {
+        self.format == OutputFormat::Pretty && self.color
+    }
+
+    /// Returns the given escape sequence, or an empty string if escapes are disabled
+    pub fn paint<T: Display>(&self, escape: T) -> String {
+        if self.is_pretty() {
+            escape.to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Returns the escape sequence for the given foreground color
+    pub fn fg(&self, value: AnsiValue) -> String {
+        self.paint(color::Fg(value))
+    }
+
+    /// Prints the given value as JSON
+    pub fn json<T: Serialize + ?Sized>(&self, value: &T) -> Result<(), serde_json::Error> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+}
+
+/// Returns how many terminal columns the text takes
+///
+/// Wide characters (like CJK or emoji) take two columns and combining marks none. Only the
+/// common ranges are covered, which is enough for lining up descriptions.
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Shortens the text to fit into the given number of columns, ending it with an ellipsis
+pub fn truncate(text: &str, width: usize) -> String {
+    if display_width(text) <= width {
+        return text.to_string();
+    }
+
+    // Leave a column for the ellipsis
+    let mut used = 0;
+    let mut truncated: String = text
+        .chars()
+        .take_while(|c| {
+            used += char_width(*c);
+            used < width
+        })
+        .collect();
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x200b..=0x200f | 0xfe00..=0xfe0f => 0,
+        0x1100..=0x115f
+        | 0x2e80..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_beats_no_color() {
+        let no_color = Some(OsStr::new("1"));
+        assert!(ColorChoice::Always.resolve(no_color, true));
+        assert!(ColorChoice::Always.resolve(no_color, false));
+    }
+
+    #[test]
+    fn no_color_beats_auto() {
+        assert!(!ColorChoice::Auto.resolve(Some(OsStr::new("1")), true));
+        assert!(ColorChoice::Auto.resolve(None, true));
+        assert!(!ColorChoice::Auto.resolve(None, false));
+    }
+
+    #[test]
+    fn empty_no_color_is_ignored() {
+        assert!(ColorChoice::Auto.resolve(Some(OsStr::new("")), true));
+    }
+
+    #[test]
+    fn never_disables_colors() {
+        assert!(!ColorChoice::Never.resolve(None, true));
+    }
+}
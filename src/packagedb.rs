@@ -0,0 +1,135 @@
+// packagedb.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use raur::Package;
+
+/// Where the AUR publishes the metadata of all packages, updated every few minutes
+const URL: &str = "https://aur.archlinux.org/packages-meta-ext-v1.json.gz";
+
+/// How long a downloaded database is used before `sync-db` downloads it again
+pub const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Returns where the decompressed database is kept inside the cache directory
+pub fn path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("packages-meta-ext-v1.json")
+}
+
+/// Returns how long ago the database was downloaded, or None if it wasn't yet
+pub fn age(cache_dir: &Path) -> Option<Duration> {
+    std::fs::metadata(path(cache_dir))
+        .and_then(|meta| meta.modified())
+        .map(|modified| modified.elapsed().unwrap_or_default())
+        .ok()
+}
+
+/// Downloads the database and replaces the cached one, returning the number of packages
+///
+/// The dump is gzip-compressed, so it is decompressed with `gzip -d` before storing it.
+/// It is only stored if it can be read, a failed download keeps the previous database.
+pub async fn sync(
+    client: &reqwest::Client,
+    cache_dir: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let compressed = client
+        .get(URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let json = gunzip(&compressed)?;
+    let packages: Vec<Package> = serde_json::from_slice(&json)?;
+
+    // Write to a temporary file first, so that searches never see a partial database
+    std::fs::create_dir_all(cache_dir)?;
+    let file = path(cache_dir);
+    let partial = file.with_extension("json.part");
+    std::fs::write(&partial, &json)?;
+    std::fs::rename(&partial, &file)?;
+
+    Ok(packages.len())
+}
+
+fn gunzip(compressed: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut child = Command::new("gzip")
+        .arg("-dc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::new(e.kind(), format!("Unable to run gzip: {}", e)))?;
+
+    // Feed gzip from another thread, as it blocks once its output isn't read
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(compressed));
+        let output = child.wait_with_output();
+        writer.join().expect("writer thread panicked").and(output)
+    })?;
+
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Unable to decompress the package database",
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Reads the cached database
+pub fn load(cache_dir: &Path) -> Result<Vec<Package>, Box<dyn std::error::Error>> {
+    let json = std::fs::read(path(cache_dir)).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => Error::new(
+            ErrorKind::NotFound,
+            "No package database found, download it with 'taur sync-db' first",
+        ),
+        _ => e,
+    })?;
+
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Returns the packages whose name or description contains the expression, ignoring case
+///
+/// With `exact`, only the package with exactly this name is returned.
+pub fn search(packages: Vec<Package>, expression: &str, exact: bool) -> Vec<Package> {
+    if exact {
+        return packages
+            .into_iter()
+            .filter(|pkg| pkg.name == expression)
+            .collect();
+    }
+
+    let expression = expression.to_lowercase();
+    packages
+        .into_iter()
+        .filter(|pkg| {
+            pkg.name.to_lowercase().contains(&expression)
+                || pkg
+                    .description
+                    .as_ref()
+                    .is_some_and(|d| d.to_lowercase().contains(&expression))
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+// progress.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::Write;
+
+use termion::{clear, color, cursor, style};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::output::Formatter;
+
+/// Receives state changes of the repositories while they are being checked
+pub type Progress = UnboundedSender<(String, RepoState)>;
+
+#[derive(Clone, Debug)]
+pub enum RepoState {
+    Fetching,
+    UpToDate,
+    Updates(usize),
+    /// Only local commits that are not upstream
+    Ahead(usize),
+    Skipped(String),
+    Failed(String),
+}
+
+/// A table of all repositories being checked, redrawn in place whenever a state changes
+///
+/// Once the table doesn't fit on the terminal anymore, e.g. after it was resized, it is removed
+/// and nothing is drawn, so only the results printed after the check are left.
+#[derive(Default)]
+pub struct StatusTable {
+    rows: Vec<(String, RepoState)>,
+    drawn: usize,
+    batched: bool,
+}
+
+impl StatusTable {
+    pub fn update(&mut self, name: String, state: RepoState) {
+        match self.rows.iter_mut().find(|(n, _)| *n == name) {
+            Some(row) => row.1 = state,
+            None => {
+                self.rows.push((name, state));
+                self.rows.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            }
+        }
+    }
+
+    pub fn draw(&mut self, out: &Formatter) {
+        if self.batched {
+            return;
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        if self.drawn > 0 {
+            let _ = write!(stdout, "{}", cursor::Up(self.drawn as u16));
+        }
+
+        // Moving up past the top of the terminal is not possible, so the table would be garbled
+        let height = termion::terminal_size().map_or(0, |(_, rows)| rows as usize);
+        if !fits(self.rows.len(), height) {
+            let _ = write!(stdout, "\r{}", clear::AfterCursor);
+            let _ = stdout.flush();
+            self.batched = true;
+            self.drawn = 0;
+            return;
+        }
+
+        let width = self
+            .rows
+            .iter()
+            .map(|(n, _)| n.len())
+            .max()
+            .unwrap_or_default();
+        for (name, state) in &self.rows {
+            let state = match state {
+                RepoState::Fetching => String::from("fetching..."),
+                RepoState::UpToDate => format!("{}up to date", out.fg(out.theme.success)),
+                RepoState::Updates(1) => format!("{}1 update", out.fg(out.theme.header)),
+                RepoState::Updates(n) => format!("{}{} updates", out.fg(out.theme.header), n),
+                RepoState::Ahead(n) => {
+                    format!("{}↑{} ahead of upstream", out.fg(out.theme.warning), n)
+                }
+                RepoState::Skipped(reason) => {
+                    format!(
+                        "{}skipped: {}",
+                        out.fg(out.theme.warning),
+                        first_line(reason)
+                    )
+                }
+                RepoState::Failed(e) => {
+                    format!("{}error: {}", out.fg(out.theme.warning), first_line(e))
+                }
+            };
+            let _ = writeln!(
+                stdout,
+                "\r{}{}{:width$}{}  {}{}",
+                clear::CurrentLine,
+                out.paint(style::Bold),
+                name,
+                out.paint(style::Reset),
+                state,
+                out.paint(color::Fg(color::Reset)),
+                width = width
+            );
+        }
+
+        self.drawn = self.rows.len();
+        let _ = stdout.flush();
+    }
+}
+
+/// Returns true if a table with this many rows can be redrawn on a terminal of the given height,
+/// which needs a line left for the cursor
+fn fits(rows: usize, height: usize) -> bool {
+    rows < height
+}
+
+/// Returns the first line of a message, so every repository keeps a single row
+fn first_line(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+/// Reports a state change if progress is being tracked
+pub fn report(progress: &Option<Progress>, name: &str, state: RepoState) {
+    if let Some(progress) = progress {
+        // The table is gone once the receiver was dropped, so there's nobody left to tell
+        let _ = progress.send((name.to_string(), state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_first_line_of_errors() {
+        assert_eq!(
+            first_line("remote rejected\nhint: try again"),
+            "remote rejected"
+        );
+        assert_eq!(first_line("single line"), "single line");
+        assert_eq!(first_line(""), "");
+    }
+
+    #[test]
+    fn table_needs_a_spare_line() {
+        assert!(fits(23, 24));
+        assert!(!fits(24, 24));
+        assert!(!fits(1, 0));
+    }
+}
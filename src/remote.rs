@@ -0,0 +1,226 @@
+// remote.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::cell::Cell;
+use std::fmt::Display;
+use std::io::Error;
+use std::path::PathBuf;
+
+use git2::{
+    AutotagOption, Cred, CredentialType, Direction, ErrorClass, ErrorCode, FetchOptions, Oid,
+    Remote, RemoteCallbacks, Repository,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PackageConfig;
+
+pub const AUR_HOST: &str = "aur.archlinux.org";
+
+/// URL scheme used for cloning AUR repositories
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloneScheme {
+    /// Anonymous https (read-only)
+    #[default]
+    Https,
+    /// SSH, authenticated with the ssh-agent or a configured key
+    Ssh,
+}
+
+impl CloneScheme {
+    pub fn url(&self, package_name: &str) -> String {
+        match self {
+            CloneScheme::Https => format!("https://{}/{}.git", AUR_HOST, package_name),
+            CloneScheme::Ssh => format!("ssh://aur@{}/{}.git", AUR_HOST, package_name),
+        }
+    }
+}
+
+/// Credentials used when cloning from and fetching remotes
+#[derive(Clone, Debug, Default)]
+pub struct RemoteAuth {
+    ssh_key: Option<PathBuf>,
+}
+
+impl RemoteAuth {
+    pub fn new(ssh_key: Option<PathBuf>) -> Self {
+        RemoteAuth { ssh_key }
+    }
+
+    /// Returns fetch options answering SSH credential requests with the configured key or,
+    /// if there is none, the ssh-agent
+    pub fn fetch_options(&self) -> FetchOptions<'_> {
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(self.callbacks());
+        options
+    }
+
+    /// Returns the callbacks used by `fetch_options`, e.g. for connecting to a remote directly
+    pub fn callbacks(&self) -> RemoteCallbacks<'_> {
+        // libgit2 keeps asking as long as the credentials are rejected, so only try once
+        let attempted = Cell::new(false);
+
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |_url, username, allowed| {
+            if attempted.replace(true) || !allowed.contains(CredentialType::SSH_KEY) {
+                return Err(git2::Error::from_str("no usable credentials"));
+            }
+
+            let username = username.unwrap_or("aur");
+            match &self.ssh_key {
+                Some(key) => Cred::ssh_key(username, None, key, None),
+                None => Cred::ssh_key_from_agent(username),
+            }
+        });
+
+        callbacks
+    }
+}
+
+/// Remote branch a repository is compared with and pulled from
+pub struct Upstream {
+    pub remote: String,
+    pub branch: String,
+}
+
+impl Upstream {
+    /// Returns the upstream of the repository, as overridden by the package's settings
+    ///
+    /// Without settings, this is the branch of `origin` tracked by the checked out branch.
+    pub fn of(repo: &Repository, package: &PackageConfig) -> Upstream {
+        Upstream {
+            remote: package
+                .remote
+                .clone()
+                .unwrap_or_else(|| String::from("origin")),
+            branch: package
+                .branch
+                .clone()
+                .unwrap_or_else(|| tracked_branch(repo)),
+        }
+    }
+
+    /// Returns true if settings replace the tracked branch, so it doesn't need to be set up
+    pub fn is_overridden(package: &PackageConfig) -> bool {
+        package.remote.is_some() || package.branch.is_some()
+    }
+
+    /// Returns the name of the remote-tracking branch, like `refs/remotes/origin/master`
+    pub fn refname(&self) -> String {
+        format!("refs/remotes/{}/{}", self.remote, self.branch)
+    }
+}
+
+/// Returns the remote branch tracked by the checked out branch, or `master` if there is none
+///
+/// `clone --branch` sets up the tracking, so fetch and pull follow the branch chosen there.
+/// The tracking is still found if the checked out branch itself is missing.
+pub fn tracked_branch(repo: &Repository) -> String {
+    let tracked = repo.find_reference("HEAD").ok().and_then(|head| {
+        let upstream = repo.branch_upstream_name(head.symbolic_target()?).ok()?;
+        let (_remote, branch) = upstream
+            .as_str()?
+            .strip_prefix("refs/remotes/")?
+            .split_once('/')?;
+        Some(branch.to_string())
+    });
+
+    tracked.unwrap_or_else(|| String::from("master"))
+}
+
+/// Returns true if the upstream branch of the remote is not where it was at the last fetch
+///
+/// Like `git ls-remote`, this only asks the remote for its refs, which is much cheaper than
+/// the negotiation of a fetch.
+pub fn upstream_moved(
+    repo: &Repository,
+    remote: &mut Remote,
+    upstream: &Upstream,
+    auth: &RemoteAuth,
+) -> Result<bool, git2::Error> {
+    let branch = &upstream.branch;
+    let tracked = repo.refname_to_id(&upstream.refname()).ok();
+
+    let connection = remote.connect_auth(Direction::Fetch, Some(auth.callbacks()), None)?;
+    let refname = format!("refs/heads/{}", branch);
+    let tip = connection
+        .list()?
+        .iter()
+        .find(|head| head.name() == refname)
+        .map(|head| head.oid());
+
+    Ok(tip.is_none() || tip != tracked)
+}
+
+/// Fetches a branch of the remote at `url` and returns its tip, without updating any refs
+///
+/// Unlike a regular fetch, this only downloads the objects and skips updating the tips, so
+/// neither the remote-tracking branches nor `FETCH_HEAD` are written.
+pub fn fetch_detached(
+    repo: &Repository,
+    url: &str,
+    branch: &str,
+    auth: &RemoteAuth,
+) -> Result<Oid, git2::Error> {
+    let mut remote = repo.remote_anonymous(url)?;
+
+    let refname = format!("refs/heads/{}", branch);
+    let mut options = auth.fetch_options();
+    options.download_tags(AutotagOption::None);
+    remote.download(&[&refname], Some(&mut options))?;
+
+    let tip = remote
+        .list()?
+        .iter()
+        .find(|head| head.name() == refname)
+        .map(|head| head.oid());
+    remote.disconnect()?;
+
+    tip.ok_or_else(|| git2::Error::from_str(&format!("the remote has no branch '{}'", branch)))
+}
+
+/// A failed SSH authentication, explaining how to set it up
+#[derive(Debug)]
+pub struct AuthError(git2::Error);
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{} (SSH authentication failed: make sure ssh-agent is running and your AUR key \
+             is added with ssh-add, or set ssh_key in the config)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Converts a git error into an IO error, explaining how to set up SSH on auth failures
+pub fn explain(e: git2::Error) -> Error {
+    if e.code() == ErrorCode::Auth || e.class() == ErrorClass::Ssh {
+        Error::other(AuthError(e))
+    } else {
+        Error::other(e)
+    }
+}
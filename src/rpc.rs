@@ -0,0 +1,64 @@
+// rpc.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::HashSet;
+
+use raur::{Package, Raur};
+
+use crate::error::{self, TaurError};
+
+/// Most package names asked for in a single info request
+///
+/// The AUR RPC takes the names as arguments of one request, which gets rejected if it grows too
+/// large. raur splits only much longer lists and queries the parts one after another.
+const INFO_CHUNK: usize = 150;
+
+/// Packages returned by an info request, and the names the AUR doesn't know
+pub struct InfoResults {
+    pub found: Vec<Package>,
+    pub missing: Vec<String>,
+}
+
+/// Looks up the given packages, in chunks that are queried concurrently
+///
+/// Names the AUR doesn't return are listed in `missing`, while failed requests are an error,
+/// so a package that doesn't exist can be told apart from an unreachable AUR.
+pub async fn info<S: AsRef<str> + Send + Sync>(
+    raur: &raur::Handle,
+    names: &[S],
+) -> Result<InfoResults, TaurError> {
+    let requests = names
+        .chunks(INFO_CHUNK)
+        .map(|chunk| error::retry(move || raur.info(chunk)));
+    let found: Vec<Package> = futures::future::try_join_all(requests)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let returned: HashSet<&str> = found.iter().map(|pkg| pkg.name.as_str()).collect();
+    let missing = names
+        .iter()
+        .map(|name| name.as_ref())
+        .filter(|name| !returned.contains(name))
+        .map(str::to_string)
+        .collect();
+
+    Ok(InfoResults { found, missing })
+}
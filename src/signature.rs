@@ -0,0 +1,86 @@
+// signature.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use git2::{ErrorCode, Oid, Repository};
+use serde::Serialize;
+
+/// Result of checking the GPG signature of a commit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// Good signature from a key in the local keyring
+    Verified,
+    /// Signed, but the signature is bad or the key is unknown
+    Unverified,
+    Unsigned,
+}
+
+/// Checks the signature of the given commit with `gpg --verify`
+///
+/// Only the local keyring is used, so keys of the AUR maintainers have to be imported
+/// beforehand (e.g. with `gpg --recv-keys`).
+pub fn verify(repo: &Repository, oid: Oid) -> SignatureStatus {
+    let (signature, signed_data) = match repo.extract_signature(&oid, None) {
+        Ok(parts) => parts,
+        Err(e) if e.code() == ErrorCode::NotFound => return SignatureStatus::Unsigned,
+        Err(_) => return SignatureStatus::Unverified,
+    };
+
+    // gpg can't read the detached signature and the signed data from stdin at once
+    let signature_path = std::env::temp_dir().join(format!("taur-{}.sig", oid));
+    if std::fs::write(&signature_path, &*signature).is_err() {
+        return SignatureStatus::Unverified;
+    }
+
+    let verified = gpg_verify(&signature_path, &signed_data);
+    let _ = std::fs::remove_file(&signature_path);
+
+    if verified {
+        SignatureStatus::Verified
+    } else {
+        SignatureStatus::Unverified
+    }
+}
+
+fn gpg_verify(signature_path: &std::path::Path, signed_data: &[u8]) -> bool {
+    let child = Command::new("gpg")
+        .args(["--batch", "--quiet", "--verify"])
+        .arg(signature_path)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if stdin.write_all(signed_data).is_err() {
+            let _ = child.wait();
+            return false;
+        }
+    }
+
+    child.wait().is_ok_and(|status| status.success())
+}
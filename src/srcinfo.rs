@@ -0,0 +1,203 @@
+// srcinfo.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::path::Path;
+
+/// The parts of a `.SRCINFO` file taur cares about
+#[derive(Debug, Default)]
+pub struct Srcinfo {
+    pub pkgnames: Vec<String>,
+    pub sources: Vec<String>,
+    pub pkgver: Option<String>,
+    pub pkgrel: Option<String>,
+    pub epoch: Option<String>,
+    pub provides: Vec<String>,
+    pub depends: Vec<String>,
+    pub makedepends: Vec<String>,
+    pub checkdepends: Vec<String>,
+    /// The source and checksum arrays by their full key (like `sha256sums_x86_64`), in order
+    /// and with duplicates, as each checksum belongs to the source at the same position
+    pub source_arrays: BTreeMap<String, Vec<String>>,
+}
+
+impl Srcinfo {
+    /// Reads the `.SRCINFO` file inside the given repository
+    pub fn read(repo: &Path) -> Result<Srcinfo, Error> {
+        let content = std::fs::read_to_string(repo.join(".SRCINFO"))?;
+        Ok(Srcinfo::parse(&content))
+    }
+
+    /// Parses the content of a `.SRCINFO` file
+    ///
+    /// Values of split packages and architecture specific keys (like `depends_x86_64`) are
+    /// merged, as taur only needs to know about everything a package base could require.
+    pub fn parse(content: &str) -> Srcinfo {
+        let mut srcinfo = Srcinfo::default();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            if value.is_empty() {
+                continue;
+            }
+
+            let base = key.split_once('_').map_or(key, |(base, _)| base);
+            if base == "source" || base.ends_with("sums") {
+                let array = srcinfo.source_arrays.entry(key.to_string()).or_default();
+                array.push(value.to_string());
+            }
+
+            // Strip the architecture suffix of keys like `depends_x86_64`
+            let key = match key.split_once('_') {
+                Some((key, _)) => key,
+                None => key,
+            };
+
+            let version = match key {
+                "pkgver" => Some(&mut srcinfo.pkgver),
+                "pkgrel" => Some(&mut srcinfo.pkgrel),
+                "epoch" => Some(&mut srcinfo.epoch),
+                _ => None,
+            };
+            if let Some(version) = version {
+                version.get_or_insert_with(|| value.to_string());
+                continue;
+            }
+
+            let list = match key {
+                "pkgname" => &mut srcinfo.pkgnames,
+                "source" => &mut srcinfo.sources,
+                "provides" => &mut srcinfo.provides,
+                "depends" => &mut srcinfo.depends,
+                "makedepends" => &mut srcinfo.makedepends,
+                "checkdepends" => &mut srcinfo.checkdepends,
+                _ => continue,
+            };
+
+            let value = match key {
+                "pkgname" | "source" => value.to_string(),
+                _ => strip_version(value).to_string(),
+            };
+            if !list.contains(&value) {
+                list.push(value);
+            }
+        }
+
+        srcinfo
+    }
+
+    /// Returns the full version as used in package file names, like `1:2.0-3`
+    pub fn version(&self) -> Option<String> {
+        let version = format!("{}-{}", self.pkgver.as_ref()?, self.pkgrel.as_ref()?);
+        match &self.epoch {
+            Some(epoch) if epoch != "0" => Some(format!("{}:{}", epoch, version)),
+            _ => Some(version),
+        }
+    }
+
+    /// Returns true if any source is checked out from a version control system
+    pub fn has_vcs_sources(&self) -> bool {
+        self.sources.iter().any(|source| {
+            // Sources may be renamed like `name::git+https://...`
+            let url = source
+                .split_once("::")
+                .map_or(source.as_str(), |(_, url)| url);
+            VCS_SUFFIXES
+                .iter()
+                .any(|vcs| url.starts_with(&format!("{}+", &vcs[1..])))
+        })
+    }
+
+    /// Returns the names makepkg saves the remote sources of all architectures under, like
+    /// `foo-1.0.tar.gz` for `foo-1.0.tar.gz::https://...` or `foo` for `git+https://.../foo.git`
+    pub fn downloaded_files(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let sources = self
+            .source_arrays
+            .iter()
+            .filter(|(key, _)| key.starts_with("source"))
+            .flat_map(|(_, sources)| sources);
+
+        for source in sources {
+            let (name, url) = match source.split_once("::") {
+                Some((name, url)) => (Some(name), url),
+                None => (None, source.as_str()),
+            };
+            // Local files are part of the repository
+            if !url.contains("://") {
+                continue;
+            }
+
+            let name = name.unwrap_or_else(|| {
+                let url = url.split(['#', '?']).next().unwrap_or(url);
+                let base = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+                // VCS sources are cloned into a directory named after the repository
+                match url.split_once("://") {
+                    Some((scheme, _)) if scheme.contains('+') => {
+                        base.strip_suffix(".git").unwrap_or(base)
+                    }
+                    _ => base,
+                }
+            });
+            if !name.is_empty() && !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+
+        names
+    }
+
+    /// Returns all dependencies needed to build and run the package
+    pub fn all_depends(&self) -> impl Iterator<Item = &String> {
+        self.depends
+            .iter()
+            .chain(self.makedepends.iter())
+            .chain(self.checkdepends.iter())
+    }
+}
+
+/// Name suffixes of VCS packages, which are also the protocols of their sources
+const VCS_SUFFIXES: [&str; 5] = ["-git", "-hg", "-svn", "-bzr", "-fossil"];
+
+/// Returns true if the repository builds a VCS package
+///
+/// VCS packages build the latest upstream sources, so their version is only known after
+/// building and new upstream commits don't show up in the AUR repository. They are detected
+/// by their name or by the sources in `.SRCINFO`.
+pub fn is_vcs_package(repo: &Path, name: &str) -> bool {
+    VCS_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        || Srcinfo::read(repo).is_ok_and(|srcinfo| srcinfo.has_vcs_sources())
+}
+
+/// Strips a version constraint like `>=1.0` from a dependency
+pub fn strip_version(dependency: &str) -> &str {
+    match dependency.find(['<', '>', '=']) {
+        Some(index) => &dependency[..index],
+        None => dependency,
+    }
+}
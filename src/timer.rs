@@ -0,0 +1,106 @@
+// timer.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use directories::BaseDirs;
+
+pub const SERVICE: &str = "taur-fetch.service";
+pub const TIMER: &str = "taur-fetch.timer";
+
+/// Returns the directory of the systemd user units, usually `~/.config/systemd/user`
+pub fn unit_dir() -> Result<PathBuf, Error> {
+    let dirs = BaseDirs::new()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Unable to find the home directory"))?;
+    Ok(dirs.config_dir().join("systemd").join("user"))
+}
+
+/// Returns the service running `taur` with the given arguments
+pub fn service(taur: &Path, args: &[String]) -> String {
+    let mut command = quote(&taur.to_string_lossy());
+    for arg in args {
+        command.push(' ');
+        command.push_str(&quote(arg));
+    }
+
+    format!(
+        "[Unit]\n\
+         Description=Check AUR packages for upstream changes\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        command
+    )
+}
+
+/// Returns the timer starting the service every `interval` (a systemd time span like `2h`)
+pub fn timer(interval: &str) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Check AUR packages for upstream changes periodically\n\
+         \n\
+         [Timer]\n\
+         OnStartupSec=5min\n\
+         OnUnitActiveSec={}\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        interval
+    )
+}
+
+/// Checks that the interval is a time span systemd understands, like `30min` or `1h 30m`
+pub fn check_interval(interval: &str) -> Result<(), Error> {
+    let valid = interval.chars().any(|c| c.is_ascii_digit())
+        && interval
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '.');
+    if valid {
+        return Ok(());
+    }
+
+    Err(Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Invalid interval '{}', expected a time span like '30min' or '2h'",
+            interval
+        ),
+    ))
+}
+
+/// Quotes an argument for `ExecStart` if it contains anything but safe characters
+fn quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/-_.=:@+,".contains(c));
+    if safe {
+        return arg.to_string();
+    }
+
+    // systemd also expands specifiers (%) and variables ($), which must be doubled
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
@@ -0,0 +1,278 @@
+// tui.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::io::{self, Write};
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::{clear, cursor, style};
+
+use crate::output::{self, Formatter};
+
+/// A repository as shown in the list of the view
+pub struct Row {
+    pub name: String,
+    /// Abbreviated id of the checked out commit
+    pub head: String,
+    pub behind: usize,
+    pub ahead: usize,
+    pub dirty: bool,
+    /// First lines of the upstream commits not pulled yet, newest first
+    pub commits: Vec<String>,
+}
+
+/// What the user picked in the view, carried out by the caller on the regular screen
+pub enum Action {
+    Fetch,
+    Pull(String),
+    Build(String),
+    /// Open a shell inside the repository
+    Shell(String),
+    Quit,
+}
+
+const HELP: &str = "↑/↓ select  f fetch  p pull  b build  o open shell  q quit";
+
+/// A full-screen list of the repositories with the details of the selected one
+pub struct View {
+    rows: Vec<Row>,
+    selected: usize,
+    status: Option<String>,
+}
+
+impl View {
+    pub fn new(rows: Vec<Row>) -> View {
+        View {
+            rows,
+            selected: 0,
+            status: None,
+        }
+    }
+
+    /// Replaces the rows, keeping the selected repository if it is still there
+    pub fn set_rows(&mut self, rows: Vec<Row>) {
+        let name = self.rows.get(self.selected).map(|row| row.name.clone());
+        self.selected = rows
+            .iter()
+            .position(|row| Some(&row.name) == name.as_ref())
+            .unwrap_or_else(|| self.selected.min(rows.len().saturating_sub(1)));
+        self.rows = rows;
+    }
+
+    /// Sets the message shown instead of the key help until the next key press
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    /// Shows the view on the alternate screen until the user picks an action
+    ///
+    /// The terminal is restored before returning, so the action can print and prompt as usual.
+    pub fn next_action(&mut self, out: &Formatter) -> io::Result<Action> {
+        let mut screen = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+        write!(screen, "{}", cursor::Hide)?;
+        let action = self.run(&mut screen, out);
+        write!(screen, "{}", cursor::Show)?;
+        screen.flush()?;
+        action
+    }
+
+    fn run(&mut self, screen: &mut impl Write, out: &Formatter) -> io::Result<Action> {
+        self.draw(screen, out)?;
+
+        for key in io::stdin().keys() {
+            let selected = self.rows.get(self.selected).map(|row| row.name.clone());
+            let action = match key? {
+                Key::Up | Key::Char('k') => {
+                    self.selected = self.selected.saturating_sub(1);
+                    None
+                }
+                Key::Down | Key::Char('j') => {
+                    if self.selected + 1 < self.rows.len() {
+                        self.selected += 1;
+                    }
+                    None
+                }
+                Key::Home | Key::Char('g') => {
+                    self.selected = 0;
+                    None
+                }
+                Key::End | Key::Char('G') => {
+                    self.selected = self.rows.len().saturating_sub(1);
+                    None
+                }
+                Key::Char('f') => Some(Action::Fetch),
+                Key::Char('p') => selected.map(Action::Pull),
+                Key::Char('b') => selected.map(Action::Build),
+                Key::Char('o') => selected.map(Action::Shell),
+                Key::Char('q') | Key::Esc | Key::Ctrl('c') => Some(Action::Quit),
+                _ => None,
+            };
+
+            self.status = None;
+            if let Some(action) = action {
+                return Ok(action);
+            }
+            self.draw(screen, out)?;
+        }
+
+        // stdin was closed, so no more keys will come
+        Ok(Action::Quit)
+    }
+
+    fn draw(&self, screen: &mut impl Write, out: &Formatter) -> io::Result<()> {
+        let (cols, lines) = termion::terminal_size()?;
+        let (cols, lines) = (cols as usize, lines as usize);
+        write!(screen, "{}", clear::All)?;
+
+        let updates = self.rows.iter().filter(|row| row.behind > 0).count();
+        write!(
+            screen,
+            "{}{}{}",
+            cursor::Goto(1, 1),
+            out.paint(style::Bold),
+            output::truncate(
+                &format!(
+                    "taur: {} repositories, {} with updates",
+                    self.rows.len(),
+                    updates
+                ),
+                cols
+            )
+        )?;
+        write!(screen, "{}", out.paint(style::Reset))?;
+
+        // The header and the help line take one line each, with a blank line below the header
+        let height = lines.saturating_sub(3);
+        let name_width = self
+            .rows
+            .iter()
+            .map(|row| row.name.len())
+            .max()
+            .unwrap_or_default();
+        // Room for the selection marker, the name and a badge like "↓12 *"
+        let list_width = (name_width + 9).min(cols / 2);
+        let offset = (self.selected + 1).saturating_sub(height);
+
+        for (line, (index, row)) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(height)
+            .enumerate()
+        {
+            let badge = match (row.behind, row.dirty) {
+                (0, false) => String::new(),
+                (0, true) => String::from("*"),
+                (behind, false) => format!("↓{}", behind),
+                (behind, true) => format!("↓{} *", behind),
+            };
+            let name_width = list_width.saturating_sub(badge.chars().count() + 3);
+            let name = output::truncate(&row.name, name_width);
+            write!(screen, "{}", cursor::Goto(1, (line + 3) as u16))?;
+            // The marker keeps the selection visible without colors
+            if index == self.selected {
+                write!(screen, "{}> ", out.paint(style::Invert))?;
+            } else {
+                write!(screen, "  ")?;
+            }
+            write!(
+                screen,
+                "{}{}{:width$}{} {}{}{}",
+                out.fg(out.theme.package_name),
+                out.paint(style::Bold),
+                name,
+                out.paint(style::NoBold),
+                out.fg(if row.behind > 0 {
+                    out.theme.header
+                } else {
+                    out.theme.warning
+                }),
+                badge,
+                out.paint(style::Reset),
+                width = name_width
+            )?;
+        }
+
+        if let Some(row) = self.rows.get(self.selected) {
+            let left = list_width + 3;
+            let width = cols.saturating_sub(left);
+            let mut details = vec![
+                format!(
+                    "{}{}{}",
+                    out.paint(style::Bold),
+                    output::truncate(&row.name, width),
+                    out.paint(style::Reset)
+                ),
+                format!("Head: {}", row.head),
+            ];
+            if row.ahead > 0 {
+                details.push(format!("{} local commits not upstream", row.ahead));
+            }
+            if row.dirty {
+                details.push(format!(
+                    "{}Uncommitted changes{}",
+                    out.fg(out.theme.warning),
+                    out.paint(style::Reset)
+                ));
+            }
+            details.push(String::new());
+            if row.commits.is_empty() {
+                details.push(String::from("Up to date as of the last fetch"));
+            } else {
+                details.push(format!(
+                    "{} new {}:",
+                    row.behind,
+                    if row.behind == 1 { "commit" } else { "commits" }
+                ));
+                for commit in &row.commits {
+                    details.push(format!(
+                        "{}* {}{}{}",
+                        out.fg(out.theme.package_name),
+                        out.fg(out.theme.commit),
+                        output::truncate(commit, width.saturating_sub(2)),
+                        out.paint(style::Reset)
+                    ));
+                }
+            }
+
+            for (line, detail) in details.iter().take(height).enumerate() {
+                write!(
+                    screen,
+                    "{}{}",
+                    cursor::Goto(left as u16, (line + 3) as u16),
+                    detail
+                )?;
+            }
+        } else {
+            write!(screen, "{}No repositories cloned yet", cursor::Goto(1, 3))?;
+        }
+
+        let help = self.status.as_deref().unwrap_or(HELP);
+        write!(
+            screen,
+            "{}{}",
+            cursor::Goto(1, lines as u16),
+            output::truncate(help, cols)
+        )?;
+        screen.flush()
+    }
+}
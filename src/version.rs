@@ -0,0 +1,142 @@
+// version.rs
+
+// *************************************************************************
+// * Copyright (C) 2019 Arne Janbu (ajanbu@gmx.de)              *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::cmp::Ordering;
+
+/// Compares two package versions like pacman's `vercmp`
+///
+/// Versions have the form `[epoch:]pkgver[-pkgrel]`. The epoch is compared first, then the
+/// pkgver, and the pkgrel only if both versions have one.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (epoch_a, version_a, release_a) = parse_evr(a);
+    let (epoch_b, version_b, release_b) = parse_evr(b);
+
+    rpmvercmp(epoch_a, epoch_b)
+        .then_with(|| rpmvercmp(version_a, version_b))
+        .then_with(|| match (release_a, release_b) {
+            (Some(release_a), Some(release_b)) => rpmvercmp(release_a, release_b),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Splits a version into epoch (`0` if there is none), pkgver and pkgrel
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits = evr.bytes().take_while(u8::is_ascii_digit).count();
+    let (epoch, rest) = match evr[digits..].strip_prefix(':') {
+        Some(rest) if digits > 0 => (&evr[..digits], rest),
+        Some(rest) => ("0", rest),
+        None => ("0", evr),
+    };
+
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compares two version strings segment by segment, as rpm and pacman do
+///
+/// Segments are runs of digits (compared as numbers) or letters (compared as strings), anything
+/// else separates them. A numeric segment is newer than an alphabetic one, and a version with
+/// remaining segments is newer unless they start with letters (so `1.0` is newer than `1.0rc1`).
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+
+    while one < a.len() && two < b.len() {
+        let (separator_one, separator_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+        if one == a.len() || two == b.len() {
+            break;
+        }
+
+        // A longer separator means a newer version (e.g. `1..1` after `1.1`)
+        let separators = (one - separator_one).cmp(&(two - separator_two));
+        if separators != Ordering::Equal {
+            return separators;
+        }
+
+        let (start_one, start_two) = (one, two);
+        let is_num = a[one].is_ascii_digit();
+        let in_segment = |c: &u8| match is_num {
+            true => c.is_ascii_digit(),
+            false => c.is_ascii_alphabetic(),
+        };
+        while one < a.len() && in_segment(&a[one]) {
+            one += 1;
+        }
+        while two < b.len() && in_segment(&b[two]) {
+            two += 1;
+        }
+
+        // The segments are of different types, numbers are newer
+        if two == start_two {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut segment_one, mut segment_two) = (&a[start_one..one], &b[start_two..two]);
+        let order = if is_num {
+            while segment_one.first() == Some(&b'0') {
+                segment_one = &segment_one[1..];
+            }
+            while segment_two.first() == Some(&b'0') {
+                segment_two = &segment_two[1..];
+            }
+            segment_one
+                .len()
+                .cmp(&segment_two.len())
+                .then_with(|| segment_one.cmp(segment_two))
+        } else {
+            segment_one.cmp(segment_two)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+
+    let (rest_one, rest_two) = (&a[one..], &b[two..]);
+    if rest_one.is_empty() && rest_two.is_empty() {
+        return Ordering::Equal;
+    }
+
+    // Never let a remaining alphabetic segment win against nothing
+    let alpha = |rest: &[u8]| rest.first().is_some_and(u8::is_ascii_alphabetic);
+    if (rest_one.is_empty() && !alpha(rest_two)) || alpha(rest_one) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}